@@ -1,7 +1,10 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::time::parse_duration;
 
 /// Agent configuration loaded from KEY=value config files
 #[derive(Debug, Clone)]
@@ -14,10 +17,22 @@ pub struct AgentConfig {
     pub install_cmd: String,
     /// Command to launch the agent
     pub launch_cmd: String,
+    /// Command to run the agent non-interactively on $KLOTHO_PROMPT (optional)
+    pub headless_cmd: Option<String>,
+    /// Command printing the installed agent's version, run in the built image (optional)
+    pub version_cmd: Option<String>,
+    /// Command printing the version the install command would fetch now (optional)
+    pub latest_version_cmd: Option<String>,
     /// Default shell for the agent (full path)
     pub shell: String,
     /// Environment variables (space-separated KEY=value pairs)
     pub env_vars: String,
+    /// Paths under /home/agent kept in named volumes across container recreation
+    pub home_volumes: Vec<String>,
+    /// Whether home volumes belong to one session ("session") or all of the agent's ("agent")
+    pub home_volume_scope: String,
+    /// Age after which `start` warns that the agent's image needs a rebuild (optional)
+    pub max_image_age: Option<Duration>,
 }
 
 impl AgentConfig {
@@ -82,17 +97,72 @@ impl AgentConfig {
                 .get("AGENT_LAUNCH_CMD")
                 .context("missing AGENT_LAUNCH_CMD in config")?
                 .clone(),
+            headless_cmd: map.get("AGENT_HEADLESS_CMD").cloned(),
+            version_cmd: map.get("AGENT_VERSION_CMD").cloned(),
+            latest_version_cmd: map.get("AGENT_LATEST_VERSION_CMD").cloned(),
             shell: map
                 .get("AGENT_SHELL")
                 .context("missing AGENT_SHELL in config")?
                 .clone(),
             env_vars: map.get("AGENT_ENV_VARS").cloned().unwrap_or_default(),
+            home_volumes: map
+                .get("AGENT_HOME_VOLUMES")
+                .map(|paths| paths.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+            home_volume_scope: map
+                .get("AGENT_HOME_VOLUME_SCOPE")
+                .cloned()
+                .unwrap_or_else(|| "session".to_string()),
+            max_image_age: map
+                .get("AGENT_MAX_IMAGE_AGE")
+                .filter(|age| !age.is_empty())
+                .map(|age| parse_duration(age).context("invalid AGENT_MAX_IMAGE_AGE"))
+                .transpose()?,
         })
     }
+
+    /// Bash snippet exporting AGENT_ENV_VARS inside the container
+    ///
+    /// The value is written as if inside double quotes in a shell-sourceable
+    /// file, so `\$` and `\"` escapes are undone before the pairs are handed
+    /// to `export`. Variable references such as `$PATH` expand in the container.
+    pub fn env_export_snippet(&self) -> Option<String> {
+        let vars = self.env_vars.trim();
+        if vars.is_empty() {
+            return None;
+        }
+
+        let unescaped = vars
+            .replace("\\$", "$")
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\");
+        Some(format!("export {}", unescaped))
+    }
+}
+
+/// Pick the version number out of a version command's output
+///
+/// "1.0.51 (Claude Code)" and "opencode v0.3.2" give "1.0.51" and "0.3.2";
+/// output without a dotted number is kept as its first line.
+pub fn parse_version(output: &str) -> Option<String> {
+    let number = output.split_whitespace().find_map(|word| {
+        let word = word
+            .trim_start_matches('v')
+            .trim_end_matches([',', ')', ';']);
+        (word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.')).then_some(word)
+    });
+    match number {
+        Some(number) => Some(number.to_string()),
+        None => output
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string),
+    }
 }
 
 /// Discover available agents from config directory
-pub fn discover_agents(repo_dir: &PathBuf) -> Result<Vec<String>> {
+pub fn discover_agents(repo_dir: &Path) -> Result<Vec<String>> {
     let agents_dir = repo_dir.join("config/agents");
 
     if !agents_dir.exists() {
@@ -171,6 +241,49 @@ AGENT_SHELL="`whoami`"
             .contains("command substitution"));
     }
 
+    #[test]
+    fn test_parse_keyvalue_single_quoted_keeps_inner_quotes() {
+        let content = r#"
+AGENT_HEADLESS_CMD='claude -p "$KLOTHO_PROMPT"'
+"#;
+        let config = AgentConfig::from_keyvalue(content).unwrap();
+        assert_eq!(
+            config.get("AGENT_HEADLESS_CMD"),
+            Some(&"claude -p \"$KLOTHO_PROMPT\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_env_export_snippet() {
+        let mut map = HashMap::new();
+        for (k, v) in [
+            ("AGENT_NAME", "opencode"),
+            ("AGENT_DESCRIPTION", "d"),
+            ("AGENT_INSTALL_CMD", "i"),
+            ("AGENT_LAUNCH_CMD", "l"),
+            ("AGENT_SHELL", "/usr/bin/fish"),
+            ("AGENT_ENV_VARS", r#"PATH=/a:\$PATH X='{\"k\":\"v\"}'"#),
+        ] {
+            map.insert(k.to_string(), v.to_string());
+        }
+
+        let config = AgentConfig::from_map(&map).unwrap();
+        assert_eq!(
+            config.env_export_snippet(),
+            Some(r#"export PATH=/a:$PATH X='{"k":"v"}'"#.to_string())
+        );
+        assert!(config.headless_cmd.is_none());
+        assert!(config.home_volumes.is_empty());
+        assert_eq!(config.home_volume_scope, "session");
+        assert!(config.max_image_age.is_none());
+
+        map.insert("AGENT_MAX_IMAGE_AGE".to_string(), "2w".to_string());
+        let config = AgentConfig::from_map(&map).unwrap();
+        assert_eq!(config.max_image_age, Some(Duration::from_secs(14 * 86_400)));
+        map.insert("AGENT_MAX_IMAGE_AGE".to_string(), "soon".to_string());
+        assert!(AgentConfig::from_map(&map).is_err());
+    }
+
     #[test]
     fn test_parse_keyvalue_variable_expansion_allowed() {
         let content = r#"
@@ -182,4 +295,18 @@ AGENT_ENV_VARS="PATH=/home/agent/.local/bin:$PATH SHELL=/usr/bin/fish"
             Some(&"PATH=/home/agent/.local/bin:$PATH SHELL=/usr/bin/fish".to_string())
         );
     }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("1.0.51 (Claude Code)\n").as_deref(),
+            Some("1.0.51")
+        );
+        assert_eq!(parse_version("opencode v0.3.2").as_deref(), Some("0.3.2"));
+        assert_eq!(
+            parse_version("\n  nightly build\n").as_deref(),
+            Some("nightly build")
+        );
+        assert_eq!(parse_version(""), None);
+    }
 }
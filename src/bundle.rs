@@ -0,0 +1,333 @@
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::config::get_state_home;
+use crate::volume;
+
+/// Manifest at the root of every bundle, saying what it holds
+pub const MANIFEST: &str = "klotho.toml";
+
+/// Manifest of an agent image bundle (`klotho export`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageManifest {
+    pub kind: String,
+    /// klotho version that exported it
+    pub klotho_version: String,
+    pub agent: String,
+    /// Tags of the image, e.g. its build version and "latest"
+    pub tags: Vec<String>,
+    pub agent_version: Option<String>,
+    /// Unix timestamp of the export
+    pub exported: u64,
+}
+
+/// `kind` of image bundles
+pub const IMAGE_KIND: &str = "image";
+
+/// Image archive inside an image bundle, as written by `save`
+pub const IMAGE_ARCHIVE: &str = "image.tar";
+
+/// Agent config an image was built from, and the user override in use, inside a bundle
+pub const BUILD_CONFIG: &str = "agent/config.conf";
+pub const USER_CONFIG: &str = "agent/user.conf";
+
+/// Manifest of a session bundle (`klotho session export`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionManifest {
+    pub kind: String,
+    /// klotho version that exported it
+    pub klotho_version: String,
+    pub name: String,
+    pub agent: String,
+    /// Unix timestamp of the export
+    pub exported: u64,
+    /// Versioned build the session was pinned to with --image-tag
+    pub image_tag: Option<String>,
+    pub record: bool,
+    /// Project directories on the exporting machine, for reference
+    pub projects: Vec<String>,
+    pub volumes: Vec<SessionVolume>,
+}
+
+/// A home volume inside a session bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionVolume {
+    /// Path relative to /home/agent
+    pub path: String,
+    /// Shared by all of the agent's sessions (AGENT_HOME_VOLUME_SCOPE=agent)
+    pub shared: bool,
+    /// Archive of its contents inside the bundle
+    pub archive: String,
+}
+
+impl SessionVolume {
+    /// Check an entry read from a bundle before its paths are used
+    pub fn validate(&self) -> Result<()> {
+        // Exports write paths relative to /home/agent, anything else was made up
+        if volume::home_path(&self.path).context("invalid volume path in bundle")? != self.path {
+            bail!("invalid volume path '{}' in bundle", self.path);
+        }
+        let valid = self
+            .archive
+            .strip_prefix("volumes/")
+            .is_some_and(|name| !name.is_empty() && !name.contains('/') && !name.contains(".."));
+        if !valid {
+            bail!(
+                "invalid volume archive '{}' in bundle - must be inside volumes/",
+                self.archive
+            );
+        }
+        Ok(())
+    }
+}
+
+/// `kind` of session bundles
+pub const SESSION_KIND: &str = "session";
+
+/// Zellij layout of the session inside a session bundle, as dumped by `action dump-layout`
+pub const LAYOUT: &str = "layout.kdl";
+
+/// Bundles created by this process, numbering their directories
+static BUNDLES: AtomicUsize = AtomicUsize::new(0);
+
+/// A tar archive of files moved between machines, staged in a temporary directory
+///
+/// The directory is removed when the bundle is dropped.
+#[derive(Debug)]
+pub struct Bundle {
+    pub dir: PathBuf,
+}
+
+impl Drop for Bundle {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl Bundle {
+    /// Start an empty bundle
+    pub fn new() -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "klotho-bundle-{}-{}",
+            std::process::id(),
+            BUNDLES.fetch_add(1, Ordering::Relaxed)
+        ));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).context("failed to clean bundle directory")?;
+        }
+        fs::create_dir_all(&dir).context("failed to create bundle directory")?;
+        Ok(Bundle { dir })
+    }
+
+    /// Unpack a bundle archive
+    pub fn open(archive: &Path) -> Result<Self> {
+        if !archive.is_file() {
+            bail!("bundle {} not found", archive.display());
+        }
+        let bundle = Bundle::new()?;
+        tar(Command::new("tar")
+            .arg("-xf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&bundle.dir))?;
+        if !bundle.dir.join(MANIFEST).exists() {
+            bail!(
+                "{} is not a klotho bundle (no {})",
+                archive.display(),
+                MANIFEST
+            );
+        }
+        Ok(bundle)
+    }
+
+    /// Path of a file inside the bundle
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Write the manifest
+    pub fn write_manifest<T: Serialize>(&self, manifest: &T) -> Result<()> {
+        let content = toml::to_string(manifest).context("failed to serialize bundle manifest")?;
+        fs::write(self.path(MANIFEST), content).context("failed to write bundle manifest")
+    }
+
+    /// Read the manifest
+    pub fn read_manifest<T: DeserializeOwned>(&self) -> Result<T> {
+        let content =
+            fs::read_to_string(self.path(MANIFEST)).context("failed to read bundle manifest")?;
+        toml::from_str(&content).context("invalid bundle manifest")
+    }
+
+    /// Write a file into the bundle, creating its parent directories
+    pub fn write(&self, name: &str, content: impl AsRef<[u8]>) -> Result<()> {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::write(&path, content).with_context(|| format!("failed to write {}", name))
+    }
+
+    /// Read a file of the bundle, `None` if the bundle doesn't have it
+    pub fn read(&self, name: &str) -> Result<Option<String>> {
+        let path = self.path(name);
+        if !path.exists() {
+            return Ok(None);
+        }
+        fs::read_to_string(&path)
+            .map(Some)
+            .with_context(|| format!("failed to read {} from bundle", name))
+    }
+
+    /// Pack the bundle into a tar archive
+    pub fn pack(&self, archive: &Path) -> Result<()> {
+        tar(Command::new("tar")
+            .arg("-cf")
+            .arg(archive)
+            .arg("-C")
+            .arg(&self.dir)
+            .arg("."))
+    }
+}
+
+fn tar(command: &mut Command) -> Result<()> {
+    let output = command.output().context("failed to run tar")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("tar failed: {}", stderr.trim());
+    }
+    Ok(())
+}
+
+/// Check a name from a bundle before it becomes part of a path
+///
+/// Bundles may come from elsewhere, so names are limited to `[a-z0-9_-]+`.
+pub fn validate_name(what: &str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_'));
+    if !valid {
+        bail!("invalid {} '{}' - use a-z, 0-9, '-' and '_'", what, name);
+    }
+    Ok(())
+}
+
+/// File recording the image an agent's latest tag was imported as
+fn imported_path(agent: &str) -> PathBuf {
+    get_state_home().join("imported").join(agent)
+}
+
+/// Remember that an agent's image was imported rather than built here
+pub fn record_import(agent: &str, image_id: &str) -> Result<()> {
+    let path = imported_path(agent);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, image_id).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Whether an image is the one imported for an agent
+///
+/// Imported images are used as shipped: local config changes don't trigger rebuilds,
+/// which couldn't run on the offline machines bundles are for.
+pub fn is_imported(agent: &str, image_id: &str) -> bool {
+    fs::read_to_string(imported_path(agent)).is_ok_and(|id| id.trim() == image_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Manifest {
+        kind: String,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_session_manifest_roundtrip() {
+        let manifest = SessionManifest {
+            kind: SESSION_KIND.to_string(),
+            klotho_version: "0.1.0".to_string(),
+            name: "default".to_string(),
+            agent: "claude".to_string(),
+            exported: 1700000000,
+            image_tag: None,
+            record: true,
+            projects: vec!["/home/me/project".to_string()],
+            volumes: vec![SessionVolume {
+                path: ".local/share/fish".to_string(),
+                shared: false,
+                archive: "volumes/local-share-fish.tar".to_string(),
+            }],
+        };
+
+        let content = toml::to_string(&manifest).unwrap();
+        assert_eq!(
+            toml::from_str::<SessionManifest>(&content).unwrap(),
+            manifest
+        );
+    }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("agent", "claude").is_ok());
+        assert!(validate_name("agent", "my_agent-2").is_ok());
+        assert!(validate_name("agent", "").is_err());
+        assert!(validate_name("agent", "..").is_err());
+        assert!(validate_name("agent", "../../etc").is_err());
+        assert!(validate_name("agent", "a/b").is_err());
+        assert!(validate_name("agent", "Claude").is_err());
+    }
+
+    #[test]
+    fn test_session_volume_validate() {
+        let entry = |path: &str, archive: &str| SessionVolume {
+            path: path.to_string(),
+            shared: false,
+            archive: archive.to_string(),
+        };
+        assert!(entry(".cache", "volumes/cache.tar").validate().is_ok());
+        assert!(entry("../.ssh", "volumes/ssh.tar").validate().is_err());
+        assert!(entry("", "volumes/x.tar").validate().is_err());
+        assert!(entry("/etc", "volumes/etc.tar").validate().is_err());
+        assert!(entry(".cache", "../../home/u/.ssh/id_ed25519")
+            .validate()
+            .is_err());
+        assert!(entry(".cache", "volumes/../../x.tar").validate().is_err());
+        assert!(entry(".cache", "layout.kdl").validate().is_err());
+    }
+
+    #[test]
+    fn test_pack_and_open() {
+        let manifest = Manifest {
+            kind: "test".to_string(),
+            tags: vec!["latest".to_string()],
+        };
+
+        let bundle = Bundle::new().unwrap();
+        bundle.write_manifest(&manifest).unwrap();
+        bundle
+            .write("config/config.conf", "AGENT_NAME=\"x\"\n")
+            .unwrap();
+        let archive = bundle.dir.with_extension("tar");
+        bundle.pack(&archive).unwrap();
+
+        let opened = Bundle::open(&archive).unwrap();
+        let _ = fs::remove_file(&archive);
+        assert_ne!(opened.dir, bundle.dir);
+        assert_eq!(opened.read_manifest::<Manifest>().unwrap(), manifest);
+        assert_eq!(
+            opened.read("config/config.conf").unwrap().as_deref(),
+            Some("AGENT_NAME=\"x\"\n")
+        );
+        assert_eq!(opened.read("missing").unwrap(), None);
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::{bail, Result};
+use std::env;
+
+use crate::container::Runtime;
+use crate::volume::{self, VOLUME_LABEL};
+
+/// Volume label with the name of the package cache a volume holds
+pub const CACHE_NAME_LABEL: &str = "klotho.volume.cache";
+
+/// A package cache shared by all sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cache {
+    pub name: &'static str,
+    /// Path relative to /home/agent
+    pub path: &'static str,
+}
+
+/// Package caches klotho manages, matching the build cache mounts in the Containerfile
+pub const CACHES: [Cache; 4] = [
+    Cache {
+        name: "npm",
+        path: ".npm",
+    },
+    Cache {
+        name: "uv",
+        path: ".cache/uv",
+    },
+    Cache {
+        name: "pip",
+        path: ".cache/pip",
+    },
+    Cache {
+        name: "cargo",
+        path: ".cargo/registry",
+    },
+];
+
+impl Cache {
+    /// Volume holding the cache, e.g. "klotho-cache-npm"
+    pub fn volume_name(&self) -> String {
+        format!("klotho-cache-{}", self.name)
+    }
+
+    /// Where the cache is mounted inside the container
+    pub fn destination(&self) -> String {
+        format!("/home/agent/{}", self.path)
+    }
+
+    /// `-v` spec mounting the cache volume (see `HomeVolume::mount_spec`)
+    pub fn mount_spec(&self, runtime: Runtime) -> String {
+        match runtime {
+            Runtime::Podman => format!("{}:{}:U", self.volume_name(), self.destination()),
+            Runtime::Docker => format!("{}:{}", self.volume_name(), self.destination()),
+        }
+    }
+
+    fn label_args(&self) -> Vec<String> {
+        vec![
+            "--label=klotho=true".to_string(),
+            format!("--label={}=cache", VOLUME_LABEL),
+            format!("--label={}={}", CACHE_NAME_LABEL, self.name),
+        ]
+    }
+}
+
+/// Look up a cache by name, listing the known caches if it doesn't exist
+pub fn find(name: &str) -> Result<Cache> {
+    match CACHES.iter().find(|cache| cache.name == name) {
+        Some(cache) => Ok(*cache),
+        None => bail!(
+            "unknown cache '{}'\navailable caches: {}",
+            name,
+            CACHES.map(|cache| cache.name).join(", ")
+        ),
+    }
+}
+
+/// Caches to mount into sessions, from KLOTHO_CACHES (all by default, "none" for none)
+pub fn enabled() -> Result<Vec<Cache>> {
+    match env::var("KLOTHO_CACHES") {
+        Ok(value) => parse_enabled(&value),
+        Err(_) => Ok(CACHES.to_vec()),
+    }
+}
+
+fn parse_enabled(value: &str) -> Result<Vec<Cache>> {
+    let value = value.trim();
+    if value.is_empty() || value == "all" {
+        return Ok(CACHES.to_vec());
+    }
+    if value == "none" {
+        return Ok(Vec::new());
+    }
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(find)
+        .collect()
+}
+
+/// Whether a path under /home/agent is (or is inside) a shared cache
+pub fn covers(path: &str) -> bool {
+    let path = path.trim_matches('/');
+    CACHES.iter().any(|cache| {
+        path == cache.path
+            || path
+                .strip_prefix(cache.path)
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Create the enabled cache volumes and build their mount arguments
+pub fn mount_args(runtime: Runtime) -> Result<Vec<String>> {
+    let mut mount_args = Vec::new();
+    for cache in enabled()? {
+        volume::create_if_missing(runtime, &cache.volume_name(), &cache.label_args())?;
+        mount_args.push("-v".to_string());
+        mount_args.push(cache.mount_spec(runtime));
+    }
+    Ok(mount_args)
+}
+
+/// Whether a cache's volume has been created
+pub fn exists(runtime: Runtime, cache: &Cache) -> bool {
+    runtime
+        .command()
+        .args(["volume", "inspect", &cache.volume_name()])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_mount_spec() {
+        let npm = find("npm").unwrap();
+        assert_eq!(npm.volume_name(), "klotho-cache-npm");
+        assert_eq!(
+            npm.mount_spec(Runtime::Docker),
+            "klotho-cache-npm:/home/agent/.npm"
+        );
+        assert!(find("gradle").is_err());
+    }
+
+    #[test]
+    fn test_parse_enabled() {
+        assert_eq!(parse_enabled("").unwrap().len(), CACHES.len());
+        assert!(parse_enabled("none").unwrap().is_empty());
+        let names: Vec<&str> = parse_enabled("uv, cargo")
+            .unwrap()
+            .iter()
+            .map(|cache| cache.name)
+            .collect();
+        assert_eq!(names, vec!["uv", "cargo"]);
+        assert!(parse_enabled("npm,maven").is_err());
+    }
+
+    #[test]
+    fn test_covers() {
+        assert!(covers(".npm"));
+        assert!(covers(".cache/uv/"));
+        assert!(covers(".cargo/registry/index"));
+        assert!(!covers(".cache"));
+        assert!(!covers(".npmrc"));
+        assert!(!covers(".local/share/fish"));
+    }
+}
@@ -1,5 +1,8 @@
 use clap::{Parser, Subcommand};
 
+use crate::container::ContainerStatus;
+use crate::selection::Selector;
+
 #[derive(Parser)]
 #[command(name = "klotho")]
 #[command(about = "Run AI agents in isolated containers with persistent Zellij sessions")]
@@ -29,19 +32,52 @@ pub enum Commands {
         #[arg(long = "linked-dir")]
         linked_dirs: Vec<String>,
 
+        /// Record the terminal stream of every attach (asciicast v2)
+        #[arg(long)]
+        record: bool,
+
+        /// Mount a git worktree on BRANCH (created if missing) instead of the project
+        #[arg(long, value_name = "BRANCH")]
+        worktree: Option<String>,
+
+        /// Remove the session when its Zellij session exits or the container stops
+        #[arg(long = "rm", visible_alias = "ephemeral")]
+        ephemeral: bool,
+
+        /// Create the session from a versioned build instead of latest (see `klotho images`)
+        #[arg(long, value_name = "TAG")]
+        image_tag: Option<String>,
+
         /// Project paths to mount
         paths: Vec<String>,
     },
 
-    /// Stop a running session
+    /// Stop running sessions
     Stop {
-        /// Session name (default: "default")
-        #[arg(default_value = "default")]
-        name: String,
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+
+        #[command(flatten)]
+        select: SelectArgs,
     },
 
-    /// Start a stopped session and reattach
+    /// Start stopped sessions (reattaches when given a single session)
     Restart {
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+
+    /// Attach to a running session
+    Attach {
+        /// Watch without being able to type into the session
+        #[arg(long)]
+        read_only: bool,
+
         /// Session name (default: "default")
         #[arg(default_value = "default")]
         name: String,
@@ -50,34 +86,414 @@ pub enum Commands {
     /// List all sessions with status
     Ls,
 
-    /// Remove a stopped session
+    /// Show a session's image, agent version, projects and options
+    Inspect {
+        /// Session name (default: "default")
+        #[arg(default_value = "default")]
+        name: String,
+    },
+
+    /// Remove stopped sessions
     Rm {
         /// Skip confirmation prompt
         #[arg(short, long)]
         force: bool,
 
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+
+    /// Remove stopped sessions that have not run for a while
+    Prune {
+        /// Only remove sessions stopped longer than this (e.g. 12h, 7d, 2w)
+        #[arg(long, value_name = "DURATION", default_value = "7d")]
+        older_than: String,
+
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Manage session recordings
+    Recordings {
+        #[command(subcommand)]
+        command: RecordingsCommands,
+    },
+
+    /// Replay a session recording in the terminal
+    Replay {
+        /// Playback speed multiplier
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+
+        /// Cap pauses at this many seconds
+        #[arg(long)]
+        idle_limit: Option<f64>,
+
+        /// Recording ID to play (default: most recent)
+        #[arg(long)]
+        id: Option<String>,
+
         /// Session name (default: "default")
         #[arg(default_value = "default")]
         name: String,
     },
 
-    /// Build agent container image
-    Build {
-        /// Build all agents
+    /// Snapshot the project directories mounted into a session
+    Snapshot {
+        /// List existing snapshots instead of taking one
         #[arg(long)]
-        all: bool,
+        list: bool,
+
+        /// Session name
+        name: String,
 
-        /// Agent name(s) to build
+        /// Snapshot label (default: current timestamp)
+        label: Option<String>,
+    },
+
+    /// Restore a session's project directories from a snapshot
+    Rollback {
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+
+        /// Session name
+        name: String,
+
+        /// Snapshot label to restore
+        label: String,
+    },
+
+    /// Run an agent non-interactively on a prompt and collect the result
+    Run {
+        /// Agent to use (interactive selection if not specified)
+        #[arg(short, long)]
+        agent: Option<String>,
+
+        /// Prompt text
+        #[arg(long)]
+        prompt: Option<String>,
+
+        /// File containing the prompt
+        #[arg(long)]
+        prompt_file: Option<String>,
+
+        /// Directory for the result bundle (default: ~/.local/state/klotho/runs/<run>)
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Project path to mount (default: current directory)
+        path: Option<String>,
+    },
+
+    /// Run the same prompt in several parallel sessions and compare results
+    #[command(args_conflicts_with_subcommands = true)]
+    Fanout {
+        #[command(subcommand)]
+        command: Option<FanoutCommands>,
+
+        #[command(flatten)]
+        args: FanoutArgs,
+    },
+
+    /// Recreate a session on a newer image, keeping its mounts, name and labels
+    Upgrade {
+        /// Upgrade to this versioned build instead of latest
+        #[arg(long, value_name = "TAG")]
+        image_tag: Option<String>,
+
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+
+        /// Session name (default: "default")
+        #[arg(default_value = "default")]
+        name: String,
+    },
+
+    /// Point an agent's latest image back at an earlier versioned build
+    RollbackImage {
+        /// Agent whose image to roll back
+        agent: String,
+
+        /// Versioned tag to make latest (see `klotho images`)
+        tag: String,
+    },
+
+    /// Manage persistent home volumes
+    Volumes {
+        #[command(subcommand)]
+        command: VolumesCommands,
+    },
+
+    /// Manage package caches shared by all sessions
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// List klotho images with the sessions using them
+    Images {
+        #[command(subcommand)]
+        command: Option<ImagesCommands>,
+    },
+
+    /// Compare the agent versions in built images with the latest available
+    Outdated {
+        /// Agent name(s) to check (default: every built agent)
         agents: Vec<String>,
     },
 
+    /// Save an agent image and its config to a file, for machines that can't build
+    Export {
+        /// Agent whose image to export
+        agent: String,
+
+        /// Versioned build to export instead of latest
+        #[arg(long, default_value = "latest")]
+        image_tag: String,
+
+        /// Output file (default: klotho-<agent>-<tag>.tar)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Load an agent image saved with `klotho export`
+    Import {
+        /// Bundle file to load
+        file: String,
+    },
+
+    /// Move sessions between machines
+    Session {
+        #[command(subcommand)]
+        command: SessionCommands,
+    },
+
+    /// Check the container runtime, host setup, configs and images for problems
+    Doctor {
+        /// Print the checks as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Build agent container image
+    Build {
+        #[command(flatten)]
+        args: BuildArgs,
+    },
+
     /// Rebuild agent container image (no cache)
     Rebuild {
-        /// Rebuild all agents
+        #[command(flatten)]
+        args: BuildArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RecordingsCommands {
+    /// List recordings for a session
+    Ls {
+        /// Session name (default: "default")
+        #[arg(default_value = "default")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SessionCommands {
+    /// Save a session's settings, home volumes, agent config and Zellij layout to a file
+    Export {
+        /// Session name (default: "default")
+        #[arg(default_value = "default")]
+        name: String,
+
+        /// Output file (default: klotho-session-<name>.tar)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
+    /// Recreate a session saved with `klotho session export` on a project here
+    Import {
+        /// Bundle file to load
+        file: String,
+
+        /// Session name (default: the exported session's)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Project paths to mount (default: current directory)
+        paths: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// List shared package caches with their size and users
+    Ls,
+
+    /// Delete shared package caches so they start empty
+    Clear {
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+
+        /// Also clear the build cache mounts used by `klotho build`
         #[arg(long)]
-        all: bool,
+        build: bool,
 
-        /// Agent name(s) to rebuild
-        agents: Vec<String>,
+        /// Caches to clear (npm, uv, pip, cargo); all when omitted
+        names: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum VolumesCommands {
+    /// List home volumes and the sessions using them
+    Ls,
+
+    /// Remove home volumes
+    Rm {
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+
+        /// Volume names (see `klotho volumes ls`)
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ImagesCommands {
+    /// Remove dangling, legacy and old versioned images not used by any session
+    Prune {
+        /// Versioned builds to keep per agent, newest first
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        keep: usize,
+
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+/// Sessions a lifecycle command applies to
+#[derive(clap::Args)]
+pub struct SelectArgs {
+    /// Session names or glob patterns such as 'exp-*' (default: "default")
+    pub names: Vec<String>,
+
+    /// Apply to every session
+    #[arg(long, conflicts_with = "names")]
+    pub all: bool,
+
+    /// Only sessions running this agent
+    #[arg(long)]
+    pub agent: Option<String>,
+
+    /// Only sessions with this status
+    #[arg(long, value_enum)]
+    pub status: Option<StatusArg>,
+}
+
+impl From<SelectArgs> for Selector {
+    fn from(args: SelectArgs) -> Self {
+        Selector {
+            patterns: args.names,
+            all: args.all,
+            agent: args.agent,
+            status: args.status.map(|status| match status {
+                StatusArg::Running => ContainerStatus::Running,
+                StatusArg::Stopped => ContainerStatus::Stopped,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum StatusArg {
+    Running,
+    Stopped,
+}
+
+#[derive(clap::Args)]
+pub struct BuildArgs {
+    /// Build all agents
+    #[arg(long)]
+    pub all: bool,
+
+    /// Refresh versions and checksums in the tool manifest before building
+    #[arg(long)]
+    pub update_tools: bool,
+
+    /// Target platforms, e.g. linux/arm64 or linux/amd64,linux/arm64 (default: host's)
+    #[arg(long)]
+    pub platform: Vec<String>,
+
+    /// Show the raw build output instead of a spinner
+    #[arg(long, value_enum, default_value = "auto")]
+    pub progress: ProgressArg,
+
+    /// Only build agents whose latest image is older than this, e.g. 7d (default agents: every built one)
+    #[arg(long, value_name = "AGE")]
+    pub if_older_than: Option<String>,
+
+    /// Print the most recent build log of the agent instead of building
+    #[arg(long)]
+    pub log: bool,
+
+    /// Agent name(s) to build
+    pub agents: Vec<String>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum ProgressArg {
+    Auto,
+    Plain,
+}
+
+#[derive(clap::Args)]
+pub struct FanoutArgs {
+    /// Agent(s) to run (repeatable, default: claude)
+    #[arg(short, long = "agent")]
+    pub agents: Vec<String>,
+
+    /// Number of sessions per agent
+    #[arg(long, default_value_t = 1)]
+    pub count: usize,
+
+    /// Prompt text
+    #[arg(long)]
+    pub prompt: Option<String>,
+
+    /// File containing the prompt
+    #[arg(long)]
+    pub prompt_file: Option<String>,
+
+    /// Fan-out ID (default: fanout-<timestamp>)
+    #[arg(long)]
+    pub id: Option<String>,
+
+    /// Git repository to work on (default: current directory)
+    pub path: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum FanoutCommands {
+    /// Show progress and results of each session
+    Status {
+        /// Fan-out ID (default: most recent)
+        id: Option<String>,
+    },
+
+    /// Compare the resulting branches against the starting commit
+    Diff {
+        /// Fan-out ID (default: most recent)
+        id: Option<String>,
+
+        /// Show the full diff of one session
+        session: Option<String>,
     },
 }
@@ -0,0 +1,71 @@
+use anyhow::{bail, Context, Result};
+use std::process::{Command, Stdio};
+
+use crate::commands::start::attach_zellij;
+use crate::config::load_agent_config;
+use crate::container::{
+    container_status, detect_runtime, find_container, get_container_labels, zellij_session_active,
+    ContainerStatus,
+};
+use crate::selection::session_agent;
+
+pub fn run(name: String, read_only: bool, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    // Find container by session name
+    let container_name = match find_container(runtime, &name)? {
+        Some(name) => name,
+        None => bail!("Session '{}' not found", name),
+    };
+
+    // Attaching never starts a stopped session
+    match container_status(runtime, &container_name)? {
+        ContainerStatus::Running => {}
+        ContainerStatus::Stopped => {
+            bail!(
+                "Session '{}' is stopped\nStart it first: klotho restart {}",
+                name,
+                name
+            );
+        }
+        ContainerStatus::NotFound => bail!("Session '{}' not found", name),
+    }
+
+    if !read_only {
+        let labels = get_container_labels(runtime, &container_name)?;
+        let agent_type = session_agent(&labels, &container_name, &name)?;
+        let (config, _is_legacy) = load_agent_config(&agent_type)?;
+        return attach_zellij(runtime, &container_name, &name, &config);
+    }
+
+    // Watching requires a live Zellij session to observe
+    if !zellij_session_active(runtime, &container_name, &name)? {
+        bail!(
+            "Session '{}' has no active Zellij session to watch\nAttach normally first: klotho attach {}",
+            name,
+            name
+        );
+    }
+
+    println!(
+        "Watching session '{}' (read-only, Ctrl+Q to leave)...",
+        name
+    );
+
+    // zellij watch attaches as an observer that can't send input to panes
+    let mut cmd = Command::new(runtime.as_str());
+    cmd.args(["exec", "-it", &container_name, "zellij", "watch", &name]);
+
+    // This is interactive - inherit stdio for TTY
+    cmd.stdin(Stdio::inherit());
+    cmd.stdout(Stdio::inherit());
+    cmd.stderr(Stdio::inherit());
+
+    let status = cmd.status().context("Failed to attach to container")?;
+
+    if !status.success() {
+        bail!("Failed to watch session");
+    }
+
+    Ok(())
+}
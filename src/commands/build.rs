@@ -1,89 +1,598 @@
 use anyhow::{Context, Result};
 use dialoguer::MultiSelect;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use owo_colors::OwoColorize;
-use std::io::{BufRead, BufReader};
-use std::process::Stdio;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
-use crate::agent::{self, AgentConfig};
+use crate::agent::{self, parse_version, AgentConfig};
+use crate::bundle;
+use crate::config::get_state_home;
 use crate::container::{self, Runtime};
-use crate::resources;
+use crate::customization::{Customization, INPUTS_LABEL};
+use crate::image::{
+    image_age, image_id, image_labels, image_ref, list_images, run_in_image, tag_image,
+    untag_image, AGENT_LABEL, AGENT_VERSION_LABEL, DEVCONTAINER_TAG_PREFIX, IMAGE_LABEL,
+};
+use crate::resources::{self, BuildContext};
+use crate::time;
+use crate::tools;
+
+/// Platforms the Containerfile has downloads for
+const SUPPORTED_PLATFORMS: [&str; 2] = ["linux/amd64", "linux/arm64"];
+
+/// Lines from the end of the build log shown when a build fails
+const FAILURE_TAIL_LINES: usize = 30;
+
+/// Build logs kept per agent; older ones are removed after each build
+const KEPT_LOGS: usize = 10;
+
+/// Stage of the Containerfile shared by all agents
+const BASE_STAGE: &str = "base";
+
+/// Repository of base stage images built for a run of concurrent agent builds
+///
+/// Not prefixed `klotho-`: that would list it as the image of an agent named "base".
+const BASE_REPOSITORY: &str = "klotho_base";
+
+/// Containerfile in a build context, and its variant building on a built base stage
+const CONTAINERFILE: &str = "Containerfile";
+const ON_BASE_CONTAINERFILE: &str = "Containerfile.on-base";
+
+/// Containerfile adding the agent version label to a built image
+const VERSION_CONTAINERFILE: &str = "Containerfile.version";
+
+/// How build output is shown while building
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// A spinner with the current build step
+    Auto,
+    /// The raw build output as it comes
+    Plain,
+}
+
+/// What `build` and `rebuild` were asked to do
+#[derive(Debug, Clone)]
+pub struct BuildRequest {
+    pub all: bool,
+    /// Refresh the tool manifest first
+    pub update_tools: bool,
+    /// --platform values as given, possibly comma-separated
+    pub platforms: Vec<String>,
+    pub progress: Progress,
+    /// Only build agents whose latest image is older than this duration
+    pub if_older_than: Option<String>,
+    pub agents: Vec<String>,
+}
 
 /// Build command entry point
-pub fn run(
-    all: bool,
-    agents: Vec<String>,
-    no_cache: bool,
-    runtime_override: Option<&str>,
-) -> Result<()> {
+pub fn run(request: BuildRequest, no_cache: bool, runtime_override: Option<&str>) -> Result<()> {
+    let BuildRequest {
+        all,
+        update_tools,
+        platforms,
+        progress,
+        if_older_than,
+        agents,
+    } = request;
+    let max_age = if_older_than
+        .as_deref()
+        .map(time::parse_duration)
+        .transpose()?;
+
+    // Refresh the tool manifest; on its own it doesn't need to build anything
+    if update_tools {
+        eprintln!("{} Updating tool manifest", "→".cyan());
+        tools::update()?;
+        if !all && agents.is_empty() {
+            return Ok(());
+        }
+    }
+
     // Detect runtime
     let runtime = container::detect_runtime(runtime_override)?;
 
+    // --platform may be repeated or comma-separated; default to the host's
+    let platforms = if platforms.is_empty() {
+        native_platform().into_iter().collect()
+    } else {
+        parse_platforms(&platforms)?
+    };
+
+    // docker's local image store holds one platform per tag; only podman builds a
+    // manifest list locally
+    if runtime == Runtime::Docker && platforms.len() > 1 {
+        anyhow::bail!(
+            "docker can't build {} into local images at once
+build one --platform at a time, or use podman (--runtime podman) for a multi-platform manifest list",
+            platforms.join(", ")
+        );
+    }
+
     // Determine which agents to build
     let agents_to_build = if all {
         get_all_agents()?
+    } else if agents.is_empty() && max_age.is_some() {
+        // Unattended refresh (cron, systemd timers): every agent built so far
+        built_agents(runtime)?
     } else if agents.is_empty() {
         select_agents_interactive()?
     } else {
         agents
     };
 
+    let agents_to_build = match max_age {
+        Some(max_age) => stale_agents(runtime, agents_to_build, max_age)?,
+        None => agents_to_build,
+    };
+
     if agents_to_build.is_empty() {
         eprintln!("{}", "No agents selected".yellow());
         return Ok(());
     }
 
-    // Build each agent
-    for agent in agents_to_build {
-        run_build(runtime, &agent, no_cache)?;
+    build_agents(runtime, &agents_to_build, no_cache, &platforms, progress)
+}
+
+/// Agents that have a latest image
+fn built_agents(runtime: Runtime) -> Result<Vec<String>> {
+    let mut agents: Vec<String> = list_images(runtime)?
+        .into_iter()
+        .filter_map(|image| {
+            let agent = image.agent?;
+            (image.name? == image_ref(&agent, "latest")).then_some(agent)
+        })
+        .collect();
+    agents.sort();
+    agents.dedup();
+    Ok(agents)
+}
+
+/// Keep the agents whose latest image is missing or older than `max_age`
+fn stale_agents(runtime: Runtime, agents: Vec<String>, max_age: Duration) -> Result<Vec<String>> {
+    let mut stale = Vec::new();
+    for agent in agents {
+        match image_age(runtime, &image_ref(&agent, "latest")) {
+            Some(age) if age <= max_age.as_secs() => eprintln!(
+                "{} {} image is {} old, skipping",
+                "✓".green(),
+                agent.bold(),
+                time::format_age(age)
+            ),
+            _ => stale.push(agent),
+        }
     }
+    Ok(stale)
+}
 
+/// Print the most recent build log of an agent
+pub fn run_log(agents: Vec<String>) -> Result<()> {
+    let [agent] = agents.as_slice() else {
+        anyhow::bail!("--log takes exactly one agent");
+    };
+
+    let Some(path) = latest_log(agent)? else {
+        anyhow::bail!("no build log for agent: {}", agent);
+    };
+    eprintln!("{}", path.display().dimmed());
+    let content =
+        std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    std::io::stdout().write_all(&content)?;
     Ok(())
 }
 
+/// Directory holding an agent's build logs, named by build time
+fn logs_dir(agent: &str) -> PathBuf {
+    get_state_home().join("builds").join(agent)
+}
+
+/// An agent's build logs, oldest first
+fn build_logs(agent: &str) -> Result<Vec<PathBuf>> {
+    let dir = logs_dir(agent);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut logs: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    // Compact timestamps sort chronologically
+    logs.sort();
+    Ok(logs)
+}
+
+/// Path of an agent's most recent build log
+pub fn latest_log(agent: &str) -> Result<Option<PathBuf>> {
+    Ok(build_logs(agent)?.pop())
+}
+
 /// Internal build function (also used by start command for auto-build)
+///
+/// Builds for the host's platform.
 pub fn run_build(runtime: Runtime, agent: &str, no_cache: bool) -> Result<()> {
-    // Get build context (embedded or local)
-    let build_context = if resources::should_use_embedded() {
-        resources::extract_build_context()?
-    } else {
-        std::path::PathBuf::from(".")
+    let platforms: Vec<String> = native_platform().into_iter().collect();
+    build_agent(
+        BuildOptions::new(runtime, no_cache, &platforms, Progress::Auto),
+        agent,
+    )
+}
+
+/// Build agents' images for the given platforms, tagged latest plus a version
+///
+/// Agents with the same customization share a build context. Unless several
+/// platforms are built, their base stage is built once and the agent stages
+/// are built on it concurrently.
+fn build_agents(
+    runtime: Runtime,
+    agents: &[String],
+    no_cache: bool,
+    platforms: &[String],
+    progress: Progress,
+) -> Result<()> {
+    let options = BuildOptions::new(runtime, no_cache, platforms, progress);
+    if let [agent] = agents {
+        return build_agent(options, agent);
+    }
+    let options = BuildOptions {
+        parallel: true,
+        ..options
     };
 
-    // Load agent config to get build args
-    let config_content = if resources::should_use_embedded() {
-        resources::get_agent_config(agent)?
-    } else {
-        std::fs::read_to_string(format!("config/agents/{}/config.conf", agent))
-            .context(format!("Agent config not found: {}", agent))?
+    // One version for the whole invocation
+    let version = time::format_compact(time::unix_now());
+
+    let mut groups: Vec<(Customization, Vec<&str>)> = Vec::new();
+    for agent in agents {
+        let custom = Customization::load(agent, &build_config(agent)?)?;
+        match groups.iter_mut().find(|(other, _)| *other == custom) {
+            Some((_, members)) => members.push(agent),
+            None => groups.push((custom, vec![agent])),
+        }
+    }
+
+    let multi = match progress {
+        Progress::Auto => MultiProgress::new(),
+        Progress::Plain => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
     };
+    let mut results: Vec<(String, Duration, Result<()>)> = Vec::new();
 
-    let config_map = AgentConfig::from_keyvalue(&config_content)?;
-    let agent_config = AgentConfig::from_map(&config_map)?;
+    for (custom, members) in &groups {
+        let context = prepare_context(custom, members)?;
+        let inputs = custom.inputs_hash()?;
 
-    // Verify Containerfile has target stage
-    let containerfile_path = build_context.join("Containerfile");
-    let containerfile = std::fs::read_to_string(&containerfile_path)
-        .context("Failed to read Containerfile")?;
+        // Manifest lists can't serve as a base, so multi-platform builds keep the stage
+        let base = if members.len() > 1 && platforms.len() <= 1 {
+            Some(build_base(options, &context, &multi)?)
+        } else {
+            None
+        };
+        let containerfile = if base.is_some() {
+            ON_BASE_CONTAINERFILE
+        } else {
+            CONTAINERFILE
+        };
 
-    let stages = find_stages(&containerfile);
-    if !stages.contains(&agent.to_string()) {
-        anyhow::bail!(
-            "Containerfile does not contain stage '{}'\nAvailable stages: {}",
+        std::thread::scope(|scope| {
+            let builds: Vec<_> = members
+                .iter()
+                .map(|agent| {
+                    let spinner = multi.add(new_spinner(progress));
+                    spinner.set_message(format!("Building {} agent...", agent));
+                    let (context, inputs, version) = (&context, &inputs, &version);
+                    scope.spawn(move || {
+                        let started = Instant::now();
+                        let tags = [image_ref(agent, version), image_ref(agent, "latest")];
+                        let result = build_in_context(
+                            options,
+                            context,
+                            containerfile,
+                            agent,
+                            inputs,
+                            &tags,
+                            &spinner,
+                        );
+                        spinner.finish_and_clear();
+                        (agent.to_string(), started.elapsed(), result)
+                    })
+                })
+                .collect();
+            for build in builds {
+                results.push(build.join().expect("build thread panicked"));
+            }
+        });
+
+        // Agent images keep the layers; only the temporary tag goes
+        if let Some(base) = base {
+            let _ = untag_image(runtime, &base);
+        }
+    }
+
+    println!("{:<20} {:<8} {:<8} IMAGE", "AGENT", "RESULT", "TIME");
+    for (agent, elapsed, result) in &results {
+        let (status, image) = match result {
+            Ok(()) => (
+                format!("{:<8}", "built").green().to_string(),
+                image_ref(agent, &version),
+            ),
+            Err(_) => (
+                format!("{:<8}", "failed").red().to_string(),
+                "-".to_string(),
+            ),
+        };
+        println!(
+            "{:<20} {} {:<8} {}",
             agent,
-            stages.join(", ")
+            status,
+            time::format_age(elapsed.as_secs()),
+            image
         );
     }
 
-    // Prepare build command
-    let image_name = format!("klotho-{}:latest", agent);
-    let mut build_cmd = runtime.command();
+    let failures: Vec<_> = results
+        .iter()
+        .filter_map(|(_, _, result)| result.as_ref().err())
+        .collect();
+    for error in &failures {
+        eprintln!("\n{} {:#}", "✗".red(), error);
+    }
+    if !failures.is_empty() {
+        anyhow::bail!("{} of {} builds failed", failures.len(), results.len());
+    }
+    Ok(())
+}
+
+/// Build an agent's image for the given platforms, tagged latest plus a version
+fn build_agent(options: BuildOptions, agent: &str) -> Result<()> {
+    let custom = Customization::load(agent, &build_config(agent)?)?;
+
+    // Tag a version to pin or roll back to, plus latest
+    let image_name = image_ref(agent, "latest");
+    let version = time::format_compact(time::unix_now());
+    build_image(
+        options,
+        agent,
+        &custom,
+        &[image_ref(agent, &version), image_name.clone()],
+    )?;
+
+    eprintln!(
+        "{} Built {} → {} ({}, {})",
+        "✓".green(),
+        agent.bold(),
+        image_name.cyan(),
+        version,
+        if options.platforms.is_empty() {
+            "default platform".to_string()
+        } else {
+            options.platforms.join(", ")
+        }
+    );
+    Ok(())
+}
+
+/// Container platform matching the host's CPU, `None` if klotho has no image for it
+pub fn native_platform() -> Option<String> {
+    platform_for_arch(std::env::consts::ARCH).map(str::to_string)
+}
+
+fn platform_for_arch(arch: &str) -> Option<&'static str> {
+    match arch {
+        "x86_64" => Some("linux/amd64"),
+        "aarch64" => Some("linux/arm64"),
+        _ => None,
+    }
+}
+
+/// Split and validate --platform values (e.g. "linux/amd64,linux/arm64")
+fn parse_platforms(values: &[String]) -> Result<Vec<String>> {
+    let mut platforms: Vec<String> = Vec::new();
+    for platform in values.iter().flat_map(|v| v.split(',')).map(str::trim) {
+        if platform.is_empty() {
+            continue;
+        }
+        if !SUPPORTED_PLATFORMS.contains(&platform) {
+            anyhow::bail!(
+                "unsupported platform '{}'\nsupported platforms: {}",
+                platform,
+                SUPPORTED_PLATFORMS.join(", ")
+            );
+        }
+        if !platforms.iter().any(|p| p == platform) {
+            platforms.push(platform.to_string());
+        }
+    }
+    Ok(platforms)
+}
+
+/// Build an agent's image on another base image (e.g. a devcontainer's), unless already built
+///
+/// The image is tagged `klotho-<agent>:devcontainer-<hash>`, the hash covering the
+/// base image and the config customizations. Returns the image reference.
+pub(crate) fn build_on_base(runtime: Runtime, agent: &str, base: &str) -> Result<String> {
+    let mut custom = Customization::load(agent, &build_config(agent)?)?;
+    custom.base_image = Some(base.to_string());
+
+    let mut hasher = Sha256::new();
+    hasher.update(custom.inputs_hash()?);
+    hasher.update(image_id(runtime, base)?);
+    let hash = format!("{:x}", hasher.finalize());
+    let reference = image_ref(
+        agent,
+        &format!("{}{}", DEVCONTAINER_TAG_PREFIX, &hash[..12]),
+    );
+    if image_id(runtime, &reference).is_ok() {
+        return Ok(reference);
+    }
+
+    let platforms: Vec<String> = native_platform().into_iter().collect();
+    build_image(
+        BuildOptions::new(runtime, false, &platforms, Progress::Auto),
+        agent,
+        &custom,
+        std::slice::from_ref(&reference),
+    )?;
+    eprintln!(
+        "{} Built {} on {} → {}",
+        "✓".green(),
+        agent.bold(),
+        base,
+        reference.cyan()
+    );
+    Ok(reference)
+}
+
+/// Options shared by the builds of one invocation
+#[derive(Debug, Clone, Copy)]
+struct BuildOptions<'a> {
+    runtime: Runtime,
+    no_cache: bool,
+    platforms: &'a [String],
+    progress: Progress,
+    /// Several builds run at once: plain output is prefixed with the build's name
+    parallel: bool,
+}
+
+impl<'a> BuildOptions<'a> {
+    fn new(runtime: Runtime, no_cache: bool, platforms: &'a [String], progress: Progress) -> Self {
+        BuildOptions {
+            runtime,
+            no_cache,
+            platforms,
+            progress,
+            parallel: false,
+        }
+    }
+}
+
+/// Build an agent's image with a customization and tag it with every tag given
+fn build_image(
+    options: BuildOptions,
+    agent: &str,
+    custom: &Customization,
+    tags: &[String],
+) -> Result<()> {
+    let context = prepare_context(custom, &[agent])?;
+
+    let spinner = new_spinner(options.progress);
+    spinner.set_message(format!("Building {} agent...", agent));
+    let result = build_in_context(
+        options,
+        &context,
+        CONTAINERFILE,
+        agent,
+        &custom.inputs_hash()?,
+        tags,
+        &spinner,
+    );
+    spinner.finish_and_clear();
+    result
+}
+
+/// Extract a build context with a customization and check it has each agent's stage
+fn prepare_context(custom: &Customization, agents: &[&str]) -> Result<BuildContext> {
+    let context = resources::extract_build_context(custom)?;
+
+    // Verify Containerfile has target stage
+    let containerfile = std::fs::read_to_string(context.path.join(CONTAINERFILE))
+        .context("Failed to read Containerfile")?;
+    let stages = find_stages(&containerfile);
+    for agent in agents {
+        if !stages.iter().any(|stage| stage == agent) {
+            anyhow::bail!(
+                "Containerfile does not contain stage '{}'\nAvailable stages: {}",
+                agent,
+                stages.join(", ")
+            );
+        }
+    }
+
+    Ok(context)
+}
+
+/// Build the base stage on its own, for agent stages to build on
+///
+/// Writes a Containerfile into the context whose agent stages start from the
+/// built base image instead of the base stage, and returns that image's tag.
+fn build_base(
+    options: BuildOptions,
+    context: &BuildContext,
+    multi: &MultiProgress,
+) -> Result<String> {
+    let tag = format!("{}:build-{}", BASE_REPOSITORY, std::process::id());
+
+    let (build_cmd, _) = build_command(
+        options,
+        context,
+        CONTAINERFILE,
+        BASE_STAGE,
+        std::slice::from_ref(&tag),
+    );
+    let spinner = multi.add(new_spinner(options.progress));
+    spinner.set_message("Building base stage...");
+    let result = run_logged(options, build_cmd, BASE_STAGE, &spinner);
+    spinner.finish_and_clear();
+    result?;
+
+    let containerfile = std::fs::read_to_string(context.path.join(CONTAINERFILE))
+        .context("Failed to read Containerfile")?;
+    std::fs::write(
+        context.path.join(ON_BASE_CONTAINERFILE),
+        on_base_image(&containerfile, &tag),
+    )
+    .context("Failed to write Containerfile")?;
+
+    Ok(tag)
+}
+
+/// Point stages built `FROM base` at a built base image instead
+fn on_base_image(containerfile: &str, image: &str) -> String {
+    let from_base = format!("from {} as ", BASE_STAGE);
+    containerfile
+        .lines()
+        .map(|line| {
+            if line.to_lowercase().starts_with(&from_base) {
+                format!("FROM {} AS {}", image, line[from_base.len()..].trim())
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Build an agent's stage from a prepared context and tag it with every tag given
+///
+/// Several platforms produce a manifest list. podman builds those with
+/// `--manifest` under the first tag, which must not exist yet, and the other
+/// tags are added afterwards.
+fn build_in_context(
+    options: BuildOptions,
+    context: &BuildContext,
+    containerfile: &str,
+    agent: &str,
+    inputs: &str,
+    tags: &[String],
+    spinner: &ProgressBar,
+) -> Result<()> {
+    // Load agent config to get build args
+    let agent_config = AgentConfig::from_map(&build_config(agent)?)?;
+
+    let (mut build_cmd, manifest) = build_command(options, context, containerfile, agent, tags);
     build_cmd
-        .arg("build")
-        .arg("-t")
-        .arg(&image_name)
-        .arg("--target")
-        .arg(agent)
+        .arg("--label")
+        .arg(format!("{}=true", IMAGE_LABEL))
+        .arg("--label")
+        .arg(format!("{}={}", AGENT_LABEL, agent))
+        .arg("--label")
+        .arg(format!("{}={}", INPUTS_LABEL, inputs))
         .arg("--build-arg")
         .arg(format!("AGENT_NAME={}", agent_config.name))
         .arg("--build-arg")
@@ -91,62 +600,268 @@ pub fn run_build(runtime: Runtime, agent: &str, no_cache: bool) -> Result<()> {
         .arg("--build-arg")
         .arg(format!("AGENT_SHELL={}", agent_config.shell))
         .arg("--build-arg")
-        .arg(format!("AGENT_LAUNCH_CMD={}", agent_config.launch_cmd))
+        .arg(format!("AGENT_LAUNCH_CMD={}", agent_config.launch_cmd));
+
+    run_logged(options, build_cmd, agent, spinner)?;
+
+    if manifest {
+        for tag in &tags[1..] {
+            tag_image(options.runtime, &tags[0], tag)?;
+        }
+    }
+
+    // Finding the installed version means running the image, so manifest lists are skipped
+    if let (Some(version_cmd), [] | [_]) = (&agent_config.version_cmd, options.platforms) {
+        spinner.set_message(format!("Checking {} version...", agent));
+        if let Err(e) = label_agent_version(options, context, version_cmd, tags) {
+            spinner.suspend(|| {
+                eprintln!(
+                    "{} Could not record the {} version: {:#}",
+                    "!".yellow(),
+                    agent,
+                    e
+                )
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Add the version an image's agent reports to the image as a label, keeping its tags
+fn label_agent_version(
+    options: BuildOptions,
+    context: &BuildContext,
+    version_cmd: &str,
+    tags: &[String],
+) -> Result<()> {
+    let output = run_in_image(options.runtime, &tags[0], version_cmd)?;
+    let version = parse_version(&output).context("version command printed nothing")?;
+
+    // Labels can't come from a RUN step, so a metadata-only image on top adds it
+    let containerfile = context.path.join(VERSION_CONTAINERFILE);
+    std::fs::write(&containerfile, format!("FROM {}\n", tags[0]))
+        .context("Failed to write Containerfile")?;
+
+    let mut build_cmd = options.runtime.command();
+    build_cmd.arg("build");
+    for tag in tags {
+        build_cmd.arg("-t").arg(tag);
+    }
+    if let [platform] = options.platforms {
+        build_cmd.arg("--platform").arg(platform);
+    }
+    build_cmd
+        .arg("--label")
+        .arg(format!("{}={}", AGENT_VERSION_LABEL, version))
+        .arg("-f")
+        .arg(&containerfile)
+        .arg(&context.path);
+    if options.runtime == Runtime::Docker {
+        build_cmd.env("DOCKER_BUILDKIT", "1");
+    }
+
+    let output = build_cmd
+        .output()
+        .context("Failed to start build command")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to label {}: {}", tags[0], stderr.trim());
+    }
+    Ok(())
+}
+
+/// Prepare the build command for a stage; also says whether it builds a podman manifest
+fn build_command(
+    options: BuildOptions,
+    context: &BuildContext,
+    containerfile: &str,
+    target: &str,
+    tags: &[String],
+) -> (Command, bool) {
+    let manifest = options.runtime == Runtime::Podman && options.platforms.len() > 1;
+    let mut build_cmd = options.runtime.command();
+    build_cmd.arg("build");
+    if manifest {
+        build_cmd.arg("--manifest").arg(&tags[0]);
+    } else {
+        for tag in tags {
+            build_cmd.arg("-t").arg(tag);
+        }
+    }
+    if !options.platforms.is_empty() {
+        build_cmd.arg("--platform").arg(options.platforms.join(","));
+    }
+    if options.no_cache {
+        build_cmd.arg("--no-cache");
+    }
+    build_cmd
+        .arg("--target")
+        .arg(target)
         .arg("-f")
-        .arg(containerfile_path)
-        .arg(&build_context)
+        .arg(context.path.join(containerfile))
+        .arg(&context.path)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
-    if no_cache {
-        build_cmd.arg("--no-cache");
+    // Package caches are build cache mounts (RUN --mount=type=cache), which
+    // docker's legacy builder rejects
+    if options.runtime == Runtime::Docker {
+        build_cmd.env("DOCKER_BUILDKIT", "1");
     }
 
-    // Create spinner with steady tick for animation
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.cyan} {msg}")
-            .unwrap(),
-    );
-    spinner.set_message(format!("Building {} agent...", agent));
-    spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+    (build_cmd, manifest)
+}
+
+/// Spinner for a build, hidden when the raw output is shown
+fn new_spinner(progress: Progress) -> ProgressBar {
+    match progress {
+        Progress::Auto => {
+            let spinner = ProgressBar::new_spinner();
+            spinner.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.cyan} {msg}")
+                    .unwrap(),
+            );
+            spinner.enable_steady_tick(std::time::Duration::from_millis(100));
+            spinner
+        }
+        Progress::Plain => ProgressBar::hidden(),
+    }
+}
+
+/// Run a build command, saving its output to the build log of `name`
+///
+/// On failure the error carries the end of the log and the log's path.
+fn run_logged(
+    options: BuildOptions,
+    mut build_cmd: Command,
+    name: &str,
+    spinner: &ProgressBar,
+) -> Result<()> {
+    // Every build's full output goes to a log, however it is shown
+    let log_dir = logs_dir(name);
+    std::fs::create_dir_all(&log_dir)
+        .with_context(|| format!("failed to create {}", log_dir.display()))?;
+    let log_path = log_dir.join(format!("{}.log", time::format_compact(time::unix_now())));
+    let mut log = File::create(&log_path)
+        .with_context(|| format!("failed to create {}", log_path.display()))?;
 
-    // Run build and capture stderr for progress
     let mut child = build_cmd.spawn().context("Failed to start build command")?;
 
-    // Read stderr for progress updates
-    if let Some(stderr) = child.stderr.take() {
-        let reader = BufReader::new(stderr);
-        for line in reader.lines() {
-            let line = line?;
+    // stdout and stderr are read on their own threads and merged in arrival order
+    let (sender, lines) = mpsc::channel();
+    let readers: Vec<_> = [
+        child
+            .stdout
+            .take()
+            .map(|out| Box::new(out) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|err| Box::new(err) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|pipe| {
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        })
+    })
+    .collect();
+    drop(sender);
+
+    let mut tail: VecDeque<String> = VecDeque::with_capacity(FAILURE_TAIL_LINES);
+    for line in lines {
+        writeln!(log, "{}", line).context("failed to write build log")?;
 
+        match options.progress {
             // Extract step info from build output
-            if let Some(step) = extract_step_info(&line) {
-                spinner.set_message(format!("Building {}: {}", agent, step));
+            Progress::Auto => {
+                if let Some(step) = extract_step_info(&line) {
+                    spinner.set_message(format!("Building {}: {}", name, step));
+                }
+                spinner.tick();
+            }
+            Progress::Plain if options.parallel => {
+                eprintln!("{} {}", format!("[{}]", name).dimmed(), line)
             }
+            Progress::Plain => eprintln!("{}", line),
+        }
 
-            // Spin the spinner to show progress
-            spinner.tick();
+        if tail.len() == FAILURE_TAIL_LINES {
+            tail.pop_front();
         }
+        tail.push_back(line);
+    }
+    for reader in readers {
+        let _ = reader.join();
     }
 
     // Wait for completion
     let status = child.wait().context("Failed to wait for build")?;
+    prune_logs(name);
 
-    spinner.finish_and_clear();
-
-    if status.success() {
-        eprintln!(
-            "{} Built {} → {}",
-            "✓".green(),
-            agent.bold(),
-            image_name.cyan()
+    if !status.success() {
+        let tail: Vec<String> = tail.into_iter().map(|line| format!("  {}", line)).collect();
+        anyhow::bail!(
+            "Build failed for {}\n{}\n{}\nFull log: {}",
+            name,
+            format!("Last {} lines of the build log:", tail.len()).dimmed(),
+            tail.join("\n"),
+            log_path.display()
         );
-        Ok(())
+    }
+    Ok(())
+}
+
+/// Remove an agent's build logs beyond the most recent ones
+fn prune_logs(agent: &str) {
+    let Ok(logs) = build_logs(agent) else {
+        return;
+    };
+    for old in logs.iter().rev().skip(KEPT_LOGS) {
+        let _ = std::fs::remove_file(old);
+    }
+}
+
+/// Agent config file used for builds (embedded, or the repo's in development mode)
+pub(crate) fn build_config_source(agent: &str) -> Result<String> {
+    if resources::should_use_embedded() {
+        resources::get_agent_config(agent)
     } else {
-        anyhow::bail!("Build failed for agent: {}", agent);
+        std::fs::read_to_string(format!("config/agents/{}/config.conf", agent))
+            .context(format!("Agent config not found: {}", agent))
+    }
+}
+
+/// Raw agent config used for builds
+fn build_config(agent: &str) -> Result<HashMap<String, String>> {
+    AgentConfig::from_keyvalue(&build_config_source(agent)?)
+}
+
+/// Whether an agent's latest image was built from different customization inputs
+pub(crate) fn inputs_changed(runtime: Runtime, agent: &str) -> Result<bool> {
+    // Imported images can't be rebuilt where they were imported to
+    if image_id(runtime, &image_ref(agent, "latest"))
+        .is_ok_and(|id| bundle::is_imported(agent, &id))
+    {
+        return Ok(false);
     }
+
+    let custom = Customization::load(agent, &build_config(agent)?)?;
+    let current = custom.inputs_hash()?;
+
+    // Images from before the label were built without customization
+    let built = image_labels(runtime, &image_ref(agent, "latest"))
+        .ok()
+        .and_then(|labels| labels.get(INPUTS_LABEL).cloned())
+        .unwrap_or_else(|| Customization::default().inputs_hash().unwrap_or_default());
+    Ok(built != current)
 }
 
 /// Get all available agents
@@ -252,6 +967,19 @@ fn extract_step_info(line: &str) -> Option<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_platforms() {
+        let platforms = parse_platforms(&[
+            "linux/amd64,linux/arm64".to_string(),
+            "linux/amd64".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(platforms, vec!["linux/amd64", "linux/arm64"]);
+        assert!(parse_platforms(&["linux/s390x".to_string()]).is_err());
+        assert_eq!(platform_for_arch("aarch64"), Some("linux/arm64"));
+        assert_eq!(platform_for_arch("riscv64"), None);
+    }
+
     #[test]
     fn test_find_stages() {
         let containerfile = r#"
@@ -268,6 +996,15 @@ RUN install-opencode
         assert_eq!(stages, vec!["base", "claude", "opencode"]);
     }
 
+    #[test]
+    fn test_on_base_image() {
+        let containerfile = "FROM debian:bookworm-slim AS base\nRUN apt-get update\n\nFROM base AS claude\nRUN install-claude\n";
+        assert_eq!(
+            on_base_image(containerfile, "klotho_base:build-42"),
+            "FROM debian:bookworm-slim AS base\nRUN apt-get update\n\nFROM klotho_base:build-42 AS claude\nRUN install-claude\n"
+        );
+    }
+
     #[test]
     fn test_extract_step_info_podman() {
         assert_eq!(
@@ -0,0 +1,138 @@
+use anyhow::{bail, Context, Result};
+
+use crate::cache::{self, Cache, CACHES};
+use crate::container::{detect_runtime, Runtime};
+use crate::selection::{self, list_sessions, session_names};
+use crate::volume;
+
+/// List shared package caches with their size and the sessions using them
+pub fn run_ls(runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+    let enabled = cache::enabled()?;
+    let sizes = volume::sizes(runtime);
+    let sessions = list_sessions(runtime)?;
+
+    // Print table header
+    println!(
+        "{:<8} {:<22} {:<20} {:<10} USED BY",
+        "CACHE", "VOLUME", "PATH", "SIZE"
+    );
+    println!("{}", "-".repeat(80));
+
+    for cache in &CACHES {
+        let name = cache.volume_name();
+        let (size, users) = if cache::exists(runtime, cache) {
+            let users = session_names(&sessions, &volume::users(runtime, &name)?);
+            let size = sizes.get(&name).cloned().unwrap_or_else(|| "-".to_string());
+            let users = if users.is_empty() {
+                "-".to_string()
+            } else {
+                users.join(", ")
+            };
+            (size, users)
+        } else {
+            ("-".to_string(), "not created".to_string())
+        };
+
+        let name = if enabled.contains(cache) {
+            name
+        } else {
+            format!("{} (disabled)", name)
+        };
+        println!(
+            "{:<8} {:<22} {:<20} {:<10} {}",
+            cache.name, name, cache.path, size, users
+        );
+    }
+
+    Ok(())
+}
+
+/// Delete shared package cache volumes, and optionally the build cache mounts
+pub fn run_clear(
+    names: Vec<String>,
+    build: bool,
+    force: bool,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let selected: Vec<Cache> = if names.is_empty() {
+        CACHES.to_vec()
+    } else {
+        names
+            .iter()
+            .map(|name| cache::find(name))
+            .collect::<Result<_>>()?
+    };
+    let existing: Vec<Cache> = selected
+        .into_iter()
+        .filter(|cache| cache::exists(runtime, cache))
+        .collect();
+
+    // A mounted volume can't be removed; say which sessions hold it
+    for cache in &existing {
+        let users = volume::users(runtime, &cache.volume_name())?;
+        if !users.is_empty() {
+            bail!(
+                "Cache '{}' is used by {}\nStop and remove those sessions first: klotho rm",
+                cache.name,
+                users.join(", ")
+            );
+        }
+    }
+
+    if existing.is_empty() && !build {
+        println!("No cache volumes to clear.");
+        return Ok(());
+    }
+
+    // Confirm unless --force
+    if !force {
+        let mut items: Vec<String> = existing.iter().map(|cache| cache.volume_name()).collect();
+        if build {
+            items.push("build cache mounts".to_string());
+        }
+        if !selection::confirm_items("clear", "cache", &items)? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    for cache in &existing {
+        volume::remove(runtime, &cache.volume_name())?;
+        println!("Cleared: {}", cache.name);
+    }
+
+    if build {
+        clear_build_caches(runtime)?;
+    }
+
+    Ok(())
+}
+
+/// Drop the `RUN --mount=type=cache` contents kept by the image builder
+fn clear_build_caches(runtime: Runtime) -> Result<()> {
+    match runtime {
+        Runtime::Docker => {
+            let output = runtime
+                .command()
+                .args(["builder", "prune", "-f", "--filter", "type=exec.cachemount"])
+                .output()
+                .context("failed to prune build cache")?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                bail!("failed to prune build cache: {}", stderr.trim());
+            }
+            println!("Cleared: build cache mounts");
+        }
+        Runtime::Podman => {
+            // buildah keeps cache mounts in a per-user directory podman has no command for
+            println!("podman keeps build cache mounts under $TMPDIR/buildah-cache-$UID");
+            println!("(usually /var/tmp); remove that directory to clear them.");
+        }
+    }
+
+    Ok(())
+}
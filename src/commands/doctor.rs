@@ -0,0 +1,558 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::agent;
+use crate::config::{get_config_home, load_agent_config};
+use crate::container::{get_project_dirs, image_exists, runtime_version, Runtime};
+use crate::image::{image_age, image_ref};
+use crate::resources;
+use crate::selection::list_sessions;
+use crate::time::format_age;
+use crate::volume;
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One finding about the environment, with how to fix it when it's a problem
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// What was checked, e.g. "runtime" or "image claude"
+    pub check: String,
+    pub status: Status,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(check: impl Into<String>, message: impl Into<String>) -> Self {
+        Check {
+            check: check.into(),
+            status: Status::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(check: impl Into<String>, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            check: check.into(),
+            status: Status::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(check: impl Into<String>, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            check: check.into(),
+            status: Status::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Check the container runtime, host setup, configs and images klotho depends on
+///
+/// Fails when any check fails, so scripts can gate on it; warnings don't.
+pub fn run(json: bool, runtime_override: Option<&str>) -> Result<()> {
+    let mut checks = Vec::new();
+
+    let runtime = check_runtime(runtime_override, &mut checks);
+    if runtime == Some(Runtime::Podman) {
+        check_rootless(&mut checks);
+    }
+    checks.push(check_userns());
+    checks.push(check_selinux());
+    checks.push(check_config_home());
+    checks.push(check_claude_json());
+
+    let (config_home, _) = get_config_home();
+    let agents = known_agents(&config_home);
+    for agent in &agents {
+        checks.push(check_agent_config(agent));
+    }
+    if let Some(runtime) = runtime {
+        for agent in &agents {
+            checks.push(check_image(runtime, agent));
+        }
+        checks.push(check_sessions(runtime));
+    }
+
+    if json {
+        let output = serde_json::to_string_pretty(&checks).context("failed to serialize checks")?;
+        println!("{}", output);
+    } else {
+        print_checks(&checks);
+    }
+
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        bail!("{} check(s) failed", failed);
+    }
+    Ok(())
+}
+
+fn print_checks(checks: &[Check]) {
+    for check in checks {
+        let mark = match check.status {
+            Status::Ok => "✓".green().to_string(),
+            Status::Warn => "!".yellow().to_string(),
+            Status::Fail => "✗".red().to_string(),
+        };
+        println!("{} {:<18} {}", mark, check.check, check.message);
+        if let Some(fix) = &check.fix {
+            println!("  {:<18} {} {}", "", "→".cyan(), fix);
+        }
+    }
+
+    let warnings = checks.iter().filter(|c| c.status == Status::Warn).count();
+    let failures = checks.iter().filter(|c| c.status == Status::Fail).count();
+    println!();
+    if warnings == 0 && failures == 0 {
+        println!("{} Everything looks good.", "✓".green());
+    } else {
+        println!("{} failed, {} warning(s)", failures, warnings);
+    }
+}
+
+/// Pick the runtime like `detect_runtime`, reporting instead of failing
+fn check_runtime(runtime_override: Option<&str>, checks: &mut Vec<Check>) -> Option<Runtime> {
+    let candidates = match runtime_override {
+        Some("podman") => vec![Runtime::Podman],
+        Some("docker") => vec![Runtime::Docker],
+        Some(other) => {
+            checks.push(Check::fail(
+                "runtime",
+                format!("invalid runtime '{}'", other),
+                "Use --runtime auto, podman or docker",
+            ));
+            return None;
+        }
+        None => vec![Runtime::Podman, Runtime::Docker],
+    };
+
+    for runtime in candidates {
+        let Some(version) = runtime_version(runtime) else {
+            continue;
+        };
+
+        // Installed isn't enough: the daemon or podman machine has to answer
+        let info = runtime.command().arg("info").output();
+        if let Some(error) = match &info {
+            Ok(output) if output.status.success() => None,
+            Ok(output) => Some(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            Err(e) => Some(e.to_string()),
+        } {
+            let fix = match runtime {
+                Runtime::Podman => {
+                    "Start the Podman machine (podman machine start) or run `podman system migrate`"
+                }
+                Runtime::Docker => {
+                    "Start the Docker daemon (sudo systemctl start docker) or Docker Desktop"
+                }
+            };
+            let error = error.lines().next().unwrap_or_default().to_string();
+            checks.push(Check::fail(
+                "runtime",
+                format!(
+                    "{} is installed but not responding: {}",
+                    runtime.as_str(),
+                    error
+                ),
+                fix,
+            ));
+            return None;
+        }
+
+        checks.push(match runtime {
+            Runtime::Podman => Check::ok("runtime", version),
+            Runtime::Docker => Check::warn(
+                "runtime",
+                version,
+                "Podman is recommended for rootless sessions: https://podman.io/getting-started/installation",
+            ),
+        });
+        return Some(runtime);
+    }
+
+    let missing = match runtime_override {
+        Some(runtime) => format!("{} not found", runtime),
+        None => "no container runtime found (podman or docker)".to_string(),
+    };
+    checks.push(Check::fail(
+        "runtime",
+        missing,
+        "Install Podman: https://podman.io/getting-started/installation",
+    ));
+    None
+}
+
+/// Rootless podman needs subordinate ID ranges for the user
+fn check_rootless(checks: &mut Vec<Check>) {
+    let rootless = Runtime::Podman
+        .command()
+        .args(["info", "--format", "{{.Host.Security.Rootless}}"])
+        .output();
+    let rootless = match rootless {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim() == "true"
+        }
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            checks.push(Check::fail(
+                "rootless",
+                format!("podman info failed: {}", stderr.trim()),
+                "Run `podman info` to see what's wrong; `podman system migrate` fixes stale setups",
+            ));
+            return;
+        }
+        Err(_) => return,
+    };
+
+    if !rootless {
+        checks.push(Check::warn(
+            "rootless",
+            "podman runs as root",
+            "Run klotho as your own user so session files are owned by you",
+        ));
+        return;
+    }
+    checks.push(Check::ok("rootless", "podman runs rootless"));
+
+    let user = current_user();
+    let uid = command_output("id", &["-u"]).unwrap_or_default();
+    let fix = format!(
+        "sudo usermod --add-subuids 100000-165535 --add-subgids 100000-165535 {} && podman system migrate",
+        user
+    );
+    for file in ["/etc/subuid", "/etc/subgid"] {
+        let content = fs::read_to_string(file).unwrap_or_default();
+        let name = file.trim_start_matches("/etc/");
+        checks.push(match subordinate_ids(&content, &user, &uid) {
+            0 => Check::fail(name, format!("no range for {} in {}", user, file), &fix),
+            count if count < 65536 => Check::warn(
+                name,
+                format!("{} IDs for {}, images may need 65536", count, user),
+                &fix,
+            ),
+            count => Check::ok(name, format!("{} IDs for {}", count, user)),
+        });
+    }
+}
+
+/// Number of subordinate IDs a user has in /etc/subuid or /etc/subgid content
+fn subordinate_ids(content: &str, user: &str, uid: &str) -> u64 {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split(':');
+            let owner = fields.next()?;
+            let _start = fields.next()?;
+            let count = fields.next()?.parse::<u64>().ok()?;
+            (owner == user || (!uid.is_empty() && owner == uid)).then_some(count)
+        })
+        .sum()
+}
+
+/// Unprivileged user namespaces, which rootless containers are built on
+fn check_userns() -> Check {
+    let max = fs::read_to_string("/proc/sys/user/max_user_namespaces")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    // Debian and Ubuntu kernels can switch them off separately
+    let clone = fs::read_to_string("/proc/sys/kernel/unprivileged_userns_clone")
+        .ok()
+        .map(|value| value.trim().to_string());
+
+    match (max, clone.as_deref()) {
+        (None, _) => Check::ok("userns", "not a Linux host, handled by the runtime's VM"),
+        (Some(0), _) => Check::fail(
+            "userns",
+            "user namespaces are disabled (user.max_user_namespaces=0)",
+            "sudo sysctl -w user.max_user_namespaces=15000, and persist it in /etc/sysctl.d",
+        ),
+        (_, Some("0")) => Check::fail(
+            "userns",
+            "unprivileged user namespaces are disabled",
+            "sudo sysctl -w kernel.unprivileged_userns_clone=1, and persist it in /etc/sysctl.d",
+        ),
+        (Some(max), _) => Check::ok("userns", format!("enabled (max {})", max)),
+    }
+}
+
+/// SELinux mode; mounts need relabeling when it's enforcing
+fn check_selinux() -> Check {
+    let mode = match fs::read_to_string("/sys/fs/selinux/enforce") {
+        Ok(value) if value.trim() == "1" => "enforcing",
+        Ok(_) => "permissive",
+        Err(_) => return Check::ok("selinux", "not enabled"),
+    };
+
+    let mounts = env::var("KLOTHO_MOUNTS").unwrap_or_default();
+    let unlabelled = unlabelled_mounts(&mounts);
+    if mode == "enforcing" && !unlabelled.is_empty() {
+        return Check::warn(
+            "selinux",
+            format!(
+                "enforcing, KLOTHO_MOUNTS without :z or :Z: {}",
+                unlabelled.join(", ")
+            ),
+            "Add :z (shared) or :Z (private) to those mounts so sessions can read them",
+        );
+    }
+    Check::ok("selinux", mode)
+}
+
+/// Bind mounts in a KLOTHO_MOUNTS value that lack an SELinux relabel option
+fn unlabelled_mounts(mounts: &str) -> Vec<&str> {
+    mounts
+        .split(',')
+        .map(str::trim)
+        .filter(|mount| mount.starts_with(['/', '~', '.']))
+        .filter(|mount| {
+            let options = mount.splitn(3, ':').nth(2).unwrap_or_default();
+            !options
+                .split(',')
+                .any(|option| option == "z" || option == "Z")
+        })
+        .collect()
+}
+
+/// Which config directory `get_config_home` picks
+fn check_config_home() -> Check {
+    let (config_home, is_legacy) = get_config_home();
+    let legacy = config_home.with_file_name("agent-session");
+
+    if is_legacy {
+        return Check::warn(
+            "config",
+            format!("using legacy {}", config_home.display()),
+            format!(
+                "mv {} {}",
+                config_home.display(),
+                config_home.with_file_name("klotho").display()
+            ),
+        );
+    }
+    if legacy.exists() {
+        return Check::warn(
+            "config",
+            format!(
+                "{} is ignored, {} takes precedence",
+                legacy.display(),
+                config_home.display()
+            ),
+            format!(
+                "Move anything you still need into {} and remove the old one",
+                config_home.display()
+            ),
+        );
+    }
+    if config_home.exists() {
+        Check::ok("config", format!("using {}", config_home.display()))
+    } else {
+        Check::ok("config", "no user config, using built-in defaults")
+    }
+}
+
+/// Host login for Claude, mounted into every session
+fn check_claude_json() -> Check {
+    let home = env::var("HOME").unwrap_or_default();
+    let path = PathBuf::from(home).join(".claude.json");
+    if path.exists() {
+        Check::ok("claude login", format!("{} found", path.display()))
+    } else {
+        Check::warn(
+            "claude login",
+            format!("{} not found, sessions will ask to log in", path.display()),
+            "Run `claude` once on the host and log in",
+        )
+    }
+}
+
+/// Agents klotho ships, plus those only configured by the user (e.g. imported)
+fn known_agents(config_home: &Path) -> Vec<String> {
+    let mut agents = if resources::should_use_embedded() {
+        resources::list_embedded_agents()
+    } else {
+        agent::discover_agents(Path::new(".")).unwrap_or_default()
+    };
+
+    if let Ok(entries) = fs::read_dir(config_home.join("agents")) {
+        for entry in entries.flatten() {
+            if entry.path().join("config.conf").is_file() {
+                agents.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    agents.sort();
+    agents.dedup();
+    agents
+}
+
+/// Whether an agent's config, with the user's override, loads
+fn check_agent_config(agent: &str) -> Check {
+    let name = format!("config {}", agent);
+    let (config_home, _) = get_config_home();
+    let user_config = config_home.join("agents").join(agent).join("config.conf");
+
+    let loaded = load_agent_config(agent)
+        .and_then(|(config, _)| volume::declared(&config, "default").map(|_| config));
+    match loaded {
+        Ok(config) if user_config.exists() => Check::ok(
+            name,
+            format!(
+                "{} (overridden by {})",
+                config.description,
+                user_config.display()
+            ),
+        ),
+        Ok(config) => Check::ok(name, config.description),
+        Err(e) => Check::fail(
+            name,
+            format!("{:#}", e),
+            format!("Fix or remove {}", user_config.display()),
+        ),
+    }
+}
+
+/// Whether an agent's image is built, and not older than its AGENT_MAX_IMAGE_AGE
+fn check_image(runtime: Runtime, agent: &str) -> Check {
+    let name = format!("image {}", agent);
+    match image_exists(runtime, agent) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Check::warn(name, "not built", format!("klotho build {}", agent));
+        }
+        Err(e) => {
+            return Check::fail(
+                name,
+                format!("{:#}", e),
+                "Check that the runtime works: klotho ls",
+            );
+        }
+    }
+
+    let age = image_age(runtime, &image_ref(agent, "latest"));
+    let max_age = load_agent_config(agent)
+        .ok()
+        .and_then(|(config, _)| config.max_image_age);
+    match (age, max_age) {
+        (Some(age), Some(max_age)) if age > max_age.as_secs() => Check::warn(
+            name,
+            format!(
+                "built {} ago (max {})",
+                format_age(age),
+                format_age(max_age.as_secs())
+            ),
+            format!("klotho rebuild {}", agent),
+        ),
+        (Some(age), _) => Check::ok(name, format!("built {} ago", format_age(age))),
+        (None, _) => Check::ok(name, "built"),
+    }
+}
+
+/// Sessions that can't be started again: their agent or project directory is gone
+fn check_sessions(runtime: Runtime) -> Check {
+    let sessions = match list_sessions(runtime) {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            return Check::fail(
+                "sessions",
+                format!("{:#}", e),
+                "Check that the runtime works",
+            )
+        }
+    };
+
+    let mut orphans: Vec<(&str, String)> = Vec::new();
+    for session in &sessions {
+        if load_agent_config(&session.agent).is_err() {
+            orphans.push((&session.name, format!("unknown agent {}", session.agent)));
+            continue;
+        }
+        let missing = get_project_dirs(runtime, &session.container)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|dir| !Path::new(dir).exists());
+        if let Some(dir) = missing {
+            orphans.push((&session.name, format!("{} is gone", dir)));
+        }
+    }
+
+    if orphans.is_empty() {
+        return Check::ok("sessions", format!("{} session(s)", sessions.len()));
+    }
+    let described: Vec<String> = orphans
+        .iter()
+        .map(|(name, reason)| format!("{} ({})", name, reason))
+        .collect();
+    let names: Vec<&str> = orphans.iter().map(|(name, _)| *name).collect();
+    Check::warn(
+        "sessions",
+        format!("orphaned: {}", described.join(", ")),
+        format!("klotho rm {}", names.join(" ")),
+    )
+}
+
+fn current_user() -> String {
+    env::var("USER")
+        .ok()
+        .filter(|user| !user.is_empty())
+        .or_else(|| command_output("id", &["-un"]))
+        .unwrap_or_default()
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subordinate_ids() {
+        let content = "alice:100000:65536\nbob:165536:65536\n1001:231072:1000\nalice:300000:10\n";
+        assert_eq!(subordinate_ids(content, "alice", "1000"), 65546);
+        assert_eq!(subordinate_ids(content, "carol", "1001"), 1000);
+        assert_eq!(subordinate_ids(content, "dave", ""), 0);
+        assert_eq!(subordinate_ids("", "alice", "1000"), 0);
+    }
+
+    #[test]
+    fn test_unlabelled_mounts() {
+        let mounts = "/data:/data:Z, /cache:/cache:z, /src:/src, named:/vol, ~/notes:/notes:ro";
+        assert_eq!(
+            unlabelled_mounts(mounts),
+            vec!["/src:/src", "~/notes:/notes:ro"]
+        );
+        assert!(unlabelled_mounts("").is_empty());
+    }
+
+    #[test]
+    fn test_check_json() {
+        let check = Check::warn("image claude", "not built", "klotho build claude");
+        let json = serde_json::to_value(&check).unwrap();
+        assert_eq!(json["check"], "image claude");
+        assert_eq!(json["status"], "warn");
+        assert_eq!(json["fix"], "klotho build claude");
+    }
+}
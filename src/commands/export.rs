@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use owo_colors::OwoColorize;
+use std::path::PathBuf;
+
+use crate::bundle::{Bundle, ImageManifest, BUILD_CONFIG, IMAGE_ARCHIVE, IMAGE_KIND, USER_CONFIG};
+use crate::commands::build;
+use crate::config::get_config_home;
+use crate::container::detect_runtime;
+use crate::image::{image_id, is_project_tag, list_images, resolve_tag};
+use crate::time::unix_now;
+
+/// Save an agent image with its config to a tar file for machines that can't build
+pub fn run(
+    agent: &str,
+    tag: &str,
+    output: Option<String>,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let reference = resolve_tag(runtime, agent, tag)?;
+    let id = image_id(runtime, &reference)?;
+    let output = PathBuf::from(output.unwrap_or_else(|| format!("klotho-{}-{}.tar", agent, tag)));
+
+    // Every tag of the image comes along, so a version tag keeps pointing at it;
+    // the saved one goes first, as loading the archive restores only that one
+    let prefix = format!("klotho-{}:", agent);
+    let images = list_images(runtime)?;
+    let mut tags: Vec<String> = vec![tag.to_string()];
+    for other in images
+        .iter()
+        .filter(|image| image.id == id)
+        .filter_map(|image| image.name.as_deref()?.strip_prefix(&prefix))
+    {
+        if !is_project_tag(other) && !tags.iter().any(|tag| tag == other) {
+            tags.push(other.to_string());
+        }
+    }
+    let agent_version = images
+        .iter()
+        .find(|image| image.id == id)
+        .and_then(|image| image.agent_version.clone());
+
+    let bundle = Bundle::new()?;
+    eprintln!("{} Saving {}", "→".cyan(), reference.cyan());
+    let save = runtime
+        .command()
+        .arg("save")
+        .arg("-o")
+        .arg(bundle.path(IMAGE_ARCHIVE))
+        .arg(&reference)
+        .output()
+        .context("failed to save image")?;
+    if !save.status.success() {
+        let stderr = String::from_utf8_lossy(&save.stderr);
+        anyhow::bail!("failed to save {}: {}", reference, stderr.trim());
+    }
+
+    write_agent_config(&bundle, agent)?;
+
+    bundle.write_manifest(&ImageManifest {
+        kind: IMAGE_KIND.to_string(),
+        klotho_version: env!("CARGO_PKG_VERSION").to_string(),
+        agent: agent.to_string(),
+        tags: tags.clone(),
+        agent_version,
+        exported: unix_now(),
+    })?;
+    bundle.pack(&output)?;
+
+    eprintln!(
+        "{} Exported {} ({}) → {}",
+        "✓".green(),
+        agent.bold(),
+        tags.join(", "),
+        output.display()
+    );
+    eprintln!("Load it elsewhere with: klotho import {}", output.display());
+    Ok(())
+}
+
+/// Add the config an agent's image is built from, plus the user's override, to a bundle
+///
+/// start needs both; the importing klotho may not ship the agent.
+pub(crate) fn write_agent_config(bundle: &Bundle, agent: &str) -> Result<()> {
+    bundle.write(BUILD_CONFIG, build::build_config_source(agent)?)?;
+    let (config_home, _) = get_config_home();
+    let user_config = config_home.join("agents").join(agent).join("config.conf");
+    if user_config.exists() {
+        let content = std::fs::read_to_string(&user_config)
+            .with_context(|| format!("failed to read {}", user_config.display()))?;
+        bundle.write(USER_CONFIG, content)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,299 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::agent::AgentConfig;
+use crate::commands::start::{create_container, ensure_image_built, shared_mount_args};
+use crate::config::load_agent_config;
+use crate::container::{
+    container_status, detect_runtime, get_image_name, remove_container, stop_container,
+    ContainerStatus, Runtime,
+};
+use crate::fanout::{self, Fanout, FanoutRun, RunState, FANOUT_LABEL};
+use crate::git;
+use crate::headless::{self, CONTAINER_TASK_DIR};
+use crate::image::AGENT_LABEL;
+use crate::time::unix_now;
+use crate::worktree;
+
+/// Start N sessions per agent on the same prompt, each on its own worktree
+pub fn run(
+    agents: Vec<String>,
+    count: usize,
+    prompt: Option<String>,
+    prompt_file: Option<String>,
+    id: Option<String>,
+    path: Option<String>,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let prompt = headless::read_prompt(prompt, prompt_file)?;
+    let agents = if agents.is_empty() {
+        vec!["claude".to_string()]
+    } else {
+        agents
+    };
+    if count == 0 {
+        bail!("--count must be at least 1");
+    }
+
+    // Resolve the repository every run branches from
+    let project = match path {
+        Some(p) => PathBuf::from(p)
+            .canonicalize()
+            .context("Failed to resolve project path")?,
+        None => std::env::current_dir().context("Failed to get current directory")?,
+    };
+    let repo = git::toplevel(&project)
+        .with_context(|| format!("{} is not inside a git repository", project.display()))?;
+    let base = git::git(Path::new(&repo), &["rev-parse", "HEAD"])?;
+
+    // Check every agent up front so a bad config doesn't leave half a fan-out
+    let mut configs = Vec::new();
+    for agent in &agents {
+        let (config, _is_legacy) = load_agent_config(agent)?;
+        headless::headless_cmd(&config)?;
+        ensure_image_built(runtime, agent)?;
+        configs.push(config);
+    }
+
+    let id = id.unwrap_or_else(fanout::new_id);
+    fanout::validate_id(&id)?;
+    if fanout::fanout_dir(&id).exists() {
+        bail!("Fan-out '{}' already exists", id);
+    }
+
+    let mut fanout = Fanout {
+        id: id.clone(),
+        repo: repo.clone(),
+        base: base.clone(),
+        created: unix_now(),
+        runs: Vec::new(),
+    };
+
+    println!(
+        "Starting fan-out '{}' ({} agent{} × {}) from {}",
+        id.bold(),
+        agents.len(),
+        if agents.len() == 1 { "" } else { "s" },
+        count,
+        &base[..base.len().min(12)]
+    );
+
+    for config in &configs {
+        for n in 1..=count {
+            let session = fanout::session_name(&id, &config.name, n);
+            let branch = fanout::branch_name(&id, &config.name, n);
+
+            let wt = worktree::create(Path::new(&repo), &session, &branch)?;
+            fanout.runs.push(FanoutRun {
+                session: session.clone(),
+                agent: config.name.clone(),
+                branch: branch.clone(),
+                worktree: wt.path.display().to_string(),
+            });
+            // Record each run as soon as it exists so status can find partial fan-outs
+            fanout.save()?;
+
+            if let Err(e) = launch_run(runtime, &fanout, &session, &wt, config, &prompt) {
+                // Don't leave an orphaned container, worktree or run behind
+                let container = container_name_for(&config.name, &session);
+                let _ = stop_container(runtime, &container);
+                let _ = remove_container(runtime, &container);
+                let _ = wt.remove();
+                fanout.runs.pop();
+                fanout.save()?;
+                return Err(e);
+            }
+
+            println!("  {} {} → {}", "✓".green(), session.bold(), branch.cyan());
+        }
+    }
+
+    println!();
+    println!("Check progress: klotho fanout status {}", id);
+    println!("Compare results: klotho fanout diff {}", id);
+
+    Ok(())
+}
+
+/// Show progress and results of each run in a fan-out
+pub fn run_status(id: Option<String>, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+    let fanout = load_fanout(id)?;
+
+    println!(
+        "Fan-out '{}' from {}",
+        fanout.id.bold(),
+        &fanout.base[..fanout.base.len().min(12)]
+    );
+    println!();
+    println!(
+        "{:<36} {:<10} {:<14} {:<8} CHANGES",
+        "SESSION", "AGENT", "STATE", "COMMITS"
+    );
+    println!("{}", "-".repeat(90));
+
+    for run in &fanout.runs {
+        let state = match fanout.run_state(run) {
+            RunState::Finished(0) => format!("{:<14}", "done").green().to_string(),
+            RunState::Finished(code) => format!("{:<14}", format!("failed ({})", code))
+                .red()
+                .to_string(),
+            RunState::Running => match container_status(runtime, &container_name(run))? {
+                ContainerStatus::Running => format!("{:<14}", "running").yellow().to_string(),
+                _ => format!("{:<14}", "stopped").red().to_string(),
+            },
+        };
+
+        let commits = fanout
+            .commits(run)
+            .map(|c| c.to_string())
+            .unwrap_or_else(|_| "-".to_string());
+        let changes = fanout.shortstat(run).unwrap_or_default();
+        let changes = if changes.is_empty() {
+            "-".to_string()
+        } else {
+            changes
+        };
+
+        println!(
+            "{:<36} {:<10} {} {:<8} {}",
+            run.session, run.agent, state, commits, changes
+        );
+    }
+
+    Ok(())
+}
+
+/// Compare fan-out results: a diffstat per run, or the full diff of one run
+pub fn run_diff(id: Option<String>, session: Option<String>) -> Result<()> {
+    let fanout = load_fanout(id)?;
+    let repo = Path::new(&fanout.repo);
+
+    if let Some(session) = session {
+        let run = fanout
+            .runs
+            .iter()
+            .find(|r| r.session == session || r.branch == session)
+            .with_context(|| {
+                format!(
+                    "Session '{}' is not part of fan-out '{}'",
+                    session, fanout.id
+                )
+            })?;
+
+        // Hand the terminal to git so its pager and colours work as usual
+        let status = git::command(repo)
+            .args(["diff", &fanout.base, &run.branch])
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .status()
+            .context("failed to run git diff")?;
+
+        if !status.success() {
+            bail!("git diff failed");
+        }
+        return Ok(());
+    }
+
+    for run in &fanout.runs {
+        println!("{} ({})", run.session.bold(), run.branch.cyan());
+        let stat = git::git(repo, &["diff", "--stat", &fanout.base, &run.branch])?;
+        if stat.is_empty() {
+            println!("  (no changes)");
+        } else {
+            for line in stat.lines() {
+                println!("  {}", line);
+            }
+        }
+        println!();
+    }
+
+    println!(
+        "Full diff of one run: klotho fanout diff {} SESSION",
+        fanout.id
+    );
+    Ok(())
+}
+
+/// Create a run's container and start the agent on the prompt in the background
+fn launch_run(
+    runtime: Runtime,
+    fanout: &Fanout,
+    session: &str,
+    wt: &worktree::Worktree,
+    config: &AgentConfig,
+    prompt: &str,
+) -> Result<()> {
+    let task_dir = fanout::run_dir(&fanout.id, session);
+    std::fs::create_dir_all(&task_dir).context("failed to create task directory")?;
+
+    // Every run of the fan-out shares the repository's git dir: it takes the
+    // shared SELinux label, a private one would lock out the other containers
+    let git_dir = wt.git_common_dir()?;
+    let mut mount_args = vec![
+        "-v".to_string(),
+        format!("{}:/workspace:Z", wt.path.display()),
+        "-v".to_string(),
+        format!("{}:{}:z", git_dir.display(), git_dir.display()),
+        "-v".to_string(),
+        format!("{}:{}:Z", task_dir.display(), CONTAINER_TASK_DIR),
+    ];
+    mount_args.extend(shared_mount_args(runtime, Vec::new())?);
+
+    let mut label_args = wt.labels();
+    label_args.push(format!("--label={}={}", FANOUT_LABEL, fanout.id));
+    label_args.push(format!("--label={}={}", AGENT_LABEL, config.name));
+
+    let container = container_name_for(&config.name, session);
+    let image_name = get_image_name(runtime, &config.name)?;
+    create_container(
+        runtime,
+        &container,
+        &image_name,
+        "/workspace",
+        &label_args,
+        &mount_args,
+        false,
+    )?;
+
+    let commit_message = format!("klotho fanout {}: {}", fanout.id, config.name);
+    let script = headless::task_script(config, Some(&commit_message));
+
+    let output = runtime
+        .command()
+        .args(["exec", "-d"])
+        .args(headless::exec_env_args(config, prompt)?)
+        .args([container.as_str(), "bash", "-c", &script])
+        .output()
+        .context("failed to launch agent")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to launch agent in {}: {}", container, stderr);
+    }
+
+    Ok(())
+}
+
+fn load_fanout(id: Option<String>) -> Result<Fanout> {
+    match id {
+        Some(id) => {
+            fanout::validate_id(&id)?;
+            Fanout::load(&id)
+        }
+        None => Fanout::latest(),
+    }
+}
+
+fn container_name(run: &FanoutRun) -> String {
+    container_name_for(&run.agent, &run.session)
+}
+
+fn container_name_for(agent: &str, session: &str) -> String {
+    format!("klotho-session-{}-{}", agent, session)
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use owo_colors::OwoColorize;
+
+use crate::container::detect_runtime;
+use crate::image::{image_users, list_images, remove_image, stale_versions, untag_image, Image};
+use crate::selection::{self, list_sessions};
+use crate::time::{format_age, unix_now};
+
+/// List klotho images with the sessions using them
+pub fn run(runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let images = list_images(runtime)?;
+    if images.is_empty() {
+        println!("No images found.");
+        println!("Build one with: klotho build AGENT");
+        return Ok(());
+    }
+
+    let users = image_users(runtime, &list_sessions(runtime)?)?;
+    let now = unix_now();
+
+    // Print table header
+    println!(
+        "{:<32} {:<10} {:<12} {:<10} {:<8} SESSIONS",
+        "IMAGE", "AGENT", "VERSION", "SIZE", "BUILT"
+    );
+    println!("{}", "-".repeat(93));
+
+    for image in &images {
+        let built = image
+            .created
+            .map(|t| format!("{} ago", format_age(now.saturating_sub(t))))
+            .unwrap_or_else(|| "-".to_string());
+
+        let sessions = match users.get(&image.id) {
+            Some(names) => names.join(", "),
+            None if image.name.is_none() || image.legacy || image.is_derived() => {
+                "(unused, prunable)".dimmed().to_string()
+            }
+            None => "-".to_string(),
+        };
+
+        println!(
+            "{:<32} {:<10} {:<12} {:<10} {:<8} {}",
+            image.display_name(),
+            image.agent.as_deref().unwrap_or("-"),
+            image.agent_version.as_deref().unwrap_or("-"),
+            image.size,
+            built,
+            sessions
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove dangling, legacy, project and old versioned klotho images no session uses
+///
+/// The newest `keep` versioned builds of each agent are kept.
+pub fn run_prune(keep: usize, force: bool, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let users = image_users(runtime, &list_sessions(runtime)?)?;
+    let images = list_images(runtime)?;
+    let prunable: Vec<&Image> = images
+        .iter()
+        .filter(|image| image.name.is_none() || image.legacy || image.is_derived())
+        .chain(stale_versions(&images, keep))
+        .filter(|image| !users.contains_key(&image.id))
+        .collect();
+
+    if prunable.is_empty() {
+        println!("No unused dangling, legacy, project or old versioned images.");
+        return Ok(());
+    }
+
+    // Confirm unless --force
+    if !force {
+        let items: Vec<String> = prunable
+            .iter()
+            .map(|image| format!("{} ({})", image.display_name(), image.size))
+            .collect();
+        if !selection::confirm_items("remove", "image", &items)? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    for image in &prunable {
+        // Keep going: an image may still be a parent of another one
+        match remove_image(runtime, image.reference()) {
+            Ok(()) => println!("Removed: {}", image.display_name()),
+            Err(_) if image.version_tag().is_some() => {
+                // An old build that a project image is derived from: drop just the tag
+                match untag_image(runtime, image.reference()) {
+                    Ok(()) => println!("Untagged: {}", image.display_name()),
+                    Err(e) => eprintln!("warning: {}", e),
+                }
+            }
+            Err(e) => eprintln!("warning: {}", e),
+        }
+    }
+
+    Ok(())
+}
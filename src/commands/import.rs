@@ -0,0 +1,109 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use std::path::Path;
+
+use crate::bundle::{
+    self, Bundle, ImageManifest, BUILD_CONFIG, IMAGE_ARCHIVE, IMAGE_KIND, USER_CONFIG,
+};
+use crate::config::get_config_home;
+use crate::container::detect_runtime;
+use crate::image::{image_id, image_ref, tag_image};
+use crate::resources;
+
+/// Load an agent image exported with `klotho export` and make it usable offline
+pub fn run(file: &str, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+    let file = Path::new(file);
+
+    let bundle = Bundle::open(file)?;
+    let manifest: ImageManifest = bundle.read_manifest()?;
+    if manifest.kind != IMAGE_KIND {
+        bail!(
+            "{} holds a {} bundle, not an agent image (see: klotho session import)",
+            file.display(),
+            manifest.kind
+        );
+    }
+    bundle::validate_name("agent", &manifest.agent)?;
+    let agent = &manifest.agent;
+    let Some(first_tag) = manifest.tags.first() else {
+        bail!("{} has no image tags", file.display());
+    };
+
+    eprintln!("{} Loading {} image", "→".cyan(), agent.bold());
+    let load = runtime
+        .command()
+        .arg("load")
+        .arg("-i")
+        .arg(bundle.path(IMAGE_ARCHIVE))
+        .output()
+        .context("failed to load image")?;
+    if !load.status.success() {
+        let stderr = String::from_utf8_lossy(&load.stderr);
+        bail!("failed to load image: {}", stderr.trim());
+    }
+
+    // The archive restores the saved tag; the image's other tags are added back
+    let id = image_id(runtime, &image_ref(agent, first_tag))
+        .context("loaded image is missing its tag")?;
+    for tag in &manifest.tags[1..] {
+        tag_image(runtime, &id, &image_ref(agent, tag))?;
+    }
+    if manifest.tags.iter().any(|tag| tag == "latest") {
+        bundle::record_import(agent, &id)?;
+    }
+
+    install_config(&bundle, agent)?;
+
+    eprintln!(
+        "{} Imported {} ({}{})",
+        "✓".green(),
+        agent.bold(),
+        manifest.tags.join(", "),
+        manifest
+            .agent_version
+            .as_deref()
+            .map(|version| format!(", agent {}", version))
+            .unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Make the bundle's agent config available, leaving an existing user config alone
+pub(crate) fn install_config(bundle: &Bundle, agent: &str) -> Result<()> {
+    let (config_home, _) = get_config_home();
+    let path = config_home.join("agents").join(agent).join("config.conf");
+
+    // This klotho may not know the agent, then the build config is needed too
+    let known = resources::get_agent_config(agent).is_ok();
+    let content = match (known, bundle.read(USER_CONFIG)?) {
+        (true, None) => return Ok(()),
+        (true, Some(user)) => user,
+        (false, user) => {
+            let build = bundle
+                .read(BUILD_CONFIG)?
+                .context("bundle has no agent config")?;
+            format!("{}\n{}", build, user.unwrap_or_default())
+        }
+    };
+
+    if path.exists() {
+        if std::fs::read_to_string(&path)? != content {
+            eprintln!(
+                "{} Keeping {}, which differs from the exported agent config",
+                "!".yellow(),
+                path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(&path, content)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    eprintln!("{} Installed agent config {}", "✓".green(), path.display());
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use anyhow::{bail, Result};
+use owo_colors::OwoColorize;
+
+use crate::container::{
+    detect_runtime, get_container_labels, get_project_dirs, ContainerStatus, EPHEMERAL_LABEL,
+};
+use crate::devcontainer::DEVCONTAINER_LABEL;
+use crate::image::{latest_id, list_images, session_images};
+use crate::recording::RECORD_LABEL;
+use crate::selection::list_sessions;
+use crate::time::{format_age, unix_now};
+use crate::worktree::Worktree;
+
+/// Show a session's container, image, agent version, projects and options
+pub fn run(name: String, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let sessions = list_sessions(runtime)?;
+    let Some(session) = sessions.iter().find(|s| s.name == name) else {
+        bail!("Session '{}' not found", name);
+    };
+    let labels = get_container_labels(runtime, &session.container)?;
+
+    let status = match session.status {
+        ContainerStatus::Running => "running".green().to_string(),
+        ContainerStatus::Stopped => "stopped".red().to_string(),
+        ContainerStatus::NotFound => "unknown".yellow().to_string(),
+    };
+    println!("{:<14} {}", "Session:", session.name.bold());
+    println!("{:<14} {}", "Agent:", session.agent);
+    println!("{:<14} {}", "Status:", status);
+    println!("{:<14} {}", "Container:", session.container);
+
+    // The image as built: its tags, agent version and age
+    let image = session_images(runtime, std::slice::from_ref(session))?.remove(&session.container);
+    if let Some(image) = &image {
+        let images = list_images(runtime)?;
+        let known: Vec<_> = images.iter().filter(|i| i.id == image.id).collect();
+        let names: Vec<String> = known.iter().filter_map(|i| i.name.clone()).collect();
+        let short_id = &image.id[..image.id.len().min(12)];
+        if names.is_empty() {
+            println!("{:<14} {}", "Image:", short_id);
+        } else {
+            println!(
+                "{:<14} {} ({})",
+                "Image:",
+                names.join(", "),
+                short_id.dimmed()
+            );
+        }
+
+        let version = known.iter().find_map(|i| i.agent_version.as_deref());
+        println!("{:<14} {}", "Version:", version.unwrap_or("-"));
+
+        if let Some(created) = known.iter().find_map(|i| i.created) {
+            let age = format_age(unix_now().saturating_sub(created));
+            println!("{:<14} {} ago", "Built:", age);
+        }
+    }
+
+    let projects = get_project_dirs(runtime, &session.container)?;
+    if !projects.is_empty() {
+        println!("{:<14} {}", "Projects:", projects.join(", "));
+    }
+    if let Some(worktree) = Worktree::from_labels(&labels) {
+        println!(
+            "{:<14} {} (branch '{}')",
+            "Worktree:",
+            worktree.path.display(),
+            worktree.branch
+        );
+    }
+    if let Some(path) = labels.get(DEVCONTAINER_LABEL) {
+        println!("{:<14} {}", "Devcontainer:", path);
+    }
+
+    let mut notes = Vec::new();
+    if labels.get(EPHEMERAL_LABEL).map(String::as_str) == Some("true") {
+        notes.push("ephemeral".to_string());
+    }
+    if labels.get(RECORD_LABEL).map(String::as_str) == Some("true") {
+        notes.push("recorded".to_string());
+    }
+    if let Some(image) = &image {
+        match &image.pinned {
+            Some(tag) => notes.push(format!("pinned {}", tag)),
+            None if labels.contains_key(DEVCONTAINER_LABEL) => {}
+            None => {
+                if latest_id(runtime, &session.agent).is_some_and(|id| id != image.agent_image_id())
+                {
+                    notes.push("outdated (klotho upgrade)".yellow().to_string());
+                }
+            }
+        }
+    }
+    if !notes.is_empty() {
+        println!("{:<14} {}", "Notes:", notes.join(", "));
+    }
+
+    Ok(())
+}
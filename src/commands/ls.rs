@@ -1,59 +1,129 @@
 use anyhow::Result;
 use owo_colors::OwoColorize;
-use crate::container::{detect_runtime, list_containers, ContainerStatus};
+use std::collections::HashMap;
+use crate::container::{
+    detect_runtime, list_container_label, list_containers_with_label, zellij_client_count,
+    ContainerStatus, EPHEMERAL_LABEL,
+};
+use crate::devcontainer::DEVCONTAINER_LABEL;
+use crate::image::{is_project_tag, latest_id, list_images, session_images, Image};
+use crate::selection::list_sessions;
 
 pub fn run(runtime_override: Option<&str>) -> Result<()> {
     let runtime = detect_runtime(runtime_override)?;
 
     // List containers
-    let containers = list_containers(runtime)?;
+    let sessions = list_sessions(runtime)?;
 
-    if containers.is_empty() {
+    if sessions.is_empty() {
         println!("No sessions found.");
         return Ok(());
     }
 
+    // One lookup for all tagged containers rather than inspecting each
+    let ephemeral = list_containers_with_label(runtime, EPHEMERAL_LABEL)?;
+    // The devcontainer label holds the devcontainer.json path rather than "true"
+    let devcontainer = list_container_label(runtime, DEVCONTAINER_LABEL)?;
+    let images = session_images(runtime, &sessions)?;
+    let all_images = list_images(runtime)?;
+    let tags = image_tags(&all_images);
+    let versions: HashMap<&str, &str> = all_images
+        .iter()
+        .filter_map(|image| Some((image.id.as_str(), image.agent_version.as_deref()?)))
+        .collect();
+    let mut latest: HashMap<String, Option<String>> = HashMap::new();
+
     // Print table header
-    println!("{:<30} {:<20} {:<10}", "NAME", "AGENT", "STATUS");
-    println!("{}", "-".repeat(60));
-
-    for (container_name, status) in containers {
-        // Extract session name and agent from container name
-        let (session_name, agent) = extract_session_info(&container_name);
-
-        // Colorize status
-        let status_str = match status {
-            ContainerStatus::Running => "running".green().to_string(),
-            ContainerStatus::Stopped => "stopped".red().to_string(),
-            ContainerStatus::NotFound => "unknown".yellow().to_string(),
+    println!(
+        "{:<30} {:<20} {:<10} {:<8} {:<24} {:<12} NOTES",
+        "NAME", "AGENT", "STATUS", "CLIENTS", "IMAGE", "VERSION"
+    );
+    println!("{}", "-".repeat(123));
+
+    for session in sessions {
+        // Count attached clients (only meaningful while running)
+        let clients = if session.status == ContainerStatus::Running {
+            zellij_client_count(runtime, &session.container, &session.name)
+                .map(|count| count.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        } else {
+            "-".to_string()
+        };
+
+        // Colorize status (padded first so ANSI codes don't break alignment)
+        let status_str = match session.status {
+            ContainerStatus::Running => format!("{:<10}", "running").green().to_string(),
+            ContainerStatus::Stopped => format!("{:<10}", "stopped").red().to_string(),
+            ContainerStatus::NotFound => format!("{:<10}", "unknown").yellow().to_string(),
         };
 
-        println!("{:<30} {:<20} {:<10}", session_name, agent, status_str);
+        let mut notes = Vec::new();
+        if ephemeral.contains(&session.container) {
+            notes.push("ephemeral".dimmed().to_string());
+        }
+
+        // Sessions keep their image across rebuilds until upgraded
+        if let Some(image) = images.get(&session.container) {
+            let latest_id = latest
+                .entry(session.agent.clone())
+                .or_insert_with(|| latest_id(runtime, &session.agent));
+            match &image.pinned {
+                Some(tag) => notes.push(format!("pinned {}", tag).dimmed().to_string()),
+                // Built on the project's own image, not the agent's latest
+                None if devcontainer.contains_key(&session.container) => {
+                    notes.push("devcontainer".dimmed().to_string())
+                }
+                None if latest_id
+                    .as_ref()
+                    .is_some_and(|id| id != image.agent_image_id()) =>
+                {
+                    notes.push("outdated".yellow().to_string())
+                }
+                None => {}
+            }
+        }
+        let notes = notes.join(", ");
+
+        let version = images
+            .get(&session.container)
+            .and_then(|image| versions.get(image.id.as_str()))
+            .copied()
+            .unwrap_or("-");
+        let image = match images.get(&session.container) {
+            Some(image) => tags
+                .get(&image.id)
+                .cloned()
+                .unwrap_or_else(|| image.id[..image.id.len().min(12)].to_string()),
+            None => "-".to_string(),
+        };
+
+        println!(
+            "{:<30} {:<20} {} {:<8} {:<24} {:<12} {}",
+            session.name, session.agent, status_str, clients, image, version, notes
+        );
     }
 
     Ok(())
 }
 
-fn extract_session_info(container_name: &str) -> (String, String) {
-    // Try new naming: klotho-<agent>-<name>
-    // Example: klotho-opencode-infinite-worlds -> agent="opencode-infinite", name="worlds"
-    if let Some(rest) = container_name.strip_prefix("klotho-") {
-        // Find the last hyphen to split agent and name
-        if let Some(last_hyphen) = rest.rfind('-') {
-            let agent = &rest[..last_hyphen];
-            let name = &rest[last_hyphen + 1..];
-            return (name.to_string(), agent.to_string());
+/// Map image IDs to the most telling tag: a project image, a build version, then latest
+fn image_tags(images: &[Image]) -> HashMap<String, String> {
+    let mut tags: HashMap<String, String> = HashMap::new();
+    for image in images {
+        let Some((_, tag)) = image.name.as_deref().and_then(|name| name.split_once(':')) else {
+            continue;
+        };
+        let rank = |tag: &str| match tag {
+            "latest" => 0,
+            _ if is_project_tag(tag) => 2,
+            _ => 1,
+        };
+        let better = tags
+            .get(&image.id)
+            .is_none_or(|current| rank(tag) > rank(current));
+        if better {
+            tags.insert(image.id.clone(), tag.to_string());
         }
     }
-
-    // Try legacy naming: <agent>-<name>
-    // Example: agent-default -> agent="agent", name="default"
-    if let Some(last_hyphen) = container_name.rfind('-') {
-        let agent = &container_name[..last_hyphen];
-        let name = &container_name[last_hyphen + 1..];
-        return (name.to_string(), agent.to_string());
-    }
-
-    // Fallback: couldn't parse
-    (container_name.to_string(), "unknown".to_string())
+    tags
 }
@@ -1,6 +1,25 @@
+pub mod attach;
 pub mod build;
+pub mod cache;
+pub mod doctor;
+pub mod export;
+pub mod fanout;
+pub mod images;
+pub mod import;
+pub mod inspect;
 pub mod ls;
+pub mod outdated;
+pub mod prune;
+pub mod recordings;
+pub mod replay;
 pub mod restart;
 pub mod rm;
+pub mod rollback;
+pub mod rollback_image;
+pub mod run;
+pub mod session;
+pub mod snapshot;
 pub mod start;
 pub mod stop;
+pub mod upgrade;
+pub mod volumes;
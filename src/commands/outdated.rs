@@ -0,0 +1,82 @@
+use anyhow::Result;
+use owo_colors::OwoColorize;
+
+use crate::agent::parse_version;
+use crate::config::load_agent_config;
+use crate::container::detect_runtime;
+use crate::image::{image_ref, list_images, run_in_image};
+
+/// Compare the agent version in each agent's latest image with what its installer fetches now
+pub fn run(agents: Vec<String>, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    // Agents with a latest image, optionally narrowed to those given
+    let images: Vec<_> = list_images(runtime)?
+        .into_iter()
+        .filter(|image| {
+            image.agent.as_ref().is_some_and(|agent| {
+                image.name.as_deref() == Some(image_ref(agent, "latest").as_str())
+                    && (agents.is_empty() || agents.contains(agent))
+            })
+        })
+        .collect();
+
+    if images.is_empty() {
+        println!("No agent images found.");
+        println!("Build one with: klotho build AGENT");
+        return Ok(());
+    }
+
+    println!("{:<20} {:<12} {:<12} STATUS", "AGENT", "IMAGE", "AVAILABLE");
+    println!("{}", "-".repeat(60));
+
+    let mut outdated = Vec::new();
+    for image in &images {
+        let agent = image.agent.as_deref().unwrap_or_default();
+        let (config, _) = load_agent_config(agent)?;
+
+        // The latest image has the tools the installer needs (npm, curl)
+        let available = match &config.latest_version_cmd {
+            Some(cmd) => match run_in_image(runtime, image.reference(), cmd) {
+                Ok(output) => parse_version(&output),
+                Err(e) => {
+                    eprintln!("{} {}: {:#}", "!".yellow(), agent, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let status = match (&image.agent_version, &available) {
+            (Some(built), Some(available)) if built == available => {
+                "up to date".green().to_string()
+            }
+            (Some(_), Some(_)) => {
+                outdated.push(agent.to_string());
+                "outdated".yellow().to_string()
+            }
+            (None, _) => "unknown (no version recorded, rebuild)"
+                .dimmed()
+                .to_string(),
+            (_, None) if config.latest_version_cmd.is_none() => {
+                "unknown (no AGENT_LATEST_VERSION_CMD)".dimmed().to_string()
+            }
+            (_, None) => "unknown".dimmed().to_string(),
+        };
+
+        println!(
+            "{:<20} {:<12} {:<12} {}",
+            agent,
+            image.agent_version.as_deref().unwrap_or("-"),
+            available.as_deref().unwrap_or("-"),
+            status
+        );
+    }
+
+    if !outdated.is_empty() {
+        println!();
+        println!("Update with: klotho rebuild {}", outdated.join(" "));
+    }
+
+    Ok(())
+}
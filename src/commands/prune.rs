@@ -0,0 +1,52 @@
+use crate::commands::rm::remove_session;
+use crate::container::{container_stopped_at, detect_runtime, ContainerStatus};
+use crate::selection::{self, Session};
+use crate::time::{format_age, parse_duration, unix_now};
+use anyhow::Result;
+
+/// Remove stopped sessions that stopped longer ago than `older_than`
+pub fn run(older_than: &str, force: bool, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+    let max_age = parse_duration(older_than)?.as_secs();
+    let now = unix_now();
+
+    let mut stale: Vec<(Session, u64)> = Vec::new();
+    for session in selection::list_sessions(runtime)? {
+        if session.status != ContainerStatus::Stopped {
+            continue;
+        }
+
+        // Sessions whose stop time can't be read are left alone
+        let Some(stopped_at) = container_stopped_at(runtime, &session.container)? else {
+            continue;
+        };
+        let age = now.saturating_sub(stopped_at);
+        if age >= max_age {
+            stale.push((session, age));
+        }
+    }
+
+    if stale.is_empty() {
+        println!("No sessions stopped more than {} ago.", older_than);
+        return Ok(());
+    }
+
+    if !force {
+        let items: Vec<String> = stale
+            .iter()
+            .map(|(session, age)| {
+                format!("{}, stopped {} ago", session.describe(), format_age(*age))
+            })
+            .collect();
+        if !selection::confirm("remove", &items)? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    for (session, _) in &stale {
+        remove_session(runtime, session, force)?;
+    }
+
+    Ok(())
+}
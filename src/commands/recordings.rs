@@ -0,0 +1,77 @@
+use anyhow::Result;
+use owo_colors::OwoColorize;
+
+use crate::recording;
+
+pub fn run_ls(name: String) -> Result<()> {
+    // Convert any captures left behind by an interrupted attach
+    recording::finalize_pending(&name)?;
+
+    let recordings = recording::list_recordings(&name)?;
+
+    if recordings.is_empty() {
+        println!("No recordings found for session '{}'.", name);
+        println!(
+            "Record new sessions with: klotho start --record -n {}",
+            name
+        );
+        return Ok(());
+    }
+
+    // Print table header
+    println!(
+        "{:<14} {:<12} {:<10} {:<10}",
+        "ID", "DURATION", "SIZE", "STATUS"
+    );
+    println!("{}", "-".repeat(50));
+
+    for rec in &recordings {
+        let status = if rec.finished {
+            format!("{:<10}", "saved").green().to_string()
+        } else {
+            format!("{:<10}", "recording").yellow().to_string()
+        };
+
+        println!(
+            "{:<14} {:<12} {:<10} {}",
+            rec.id,
+            format_duration(rec.duration),
+            format_size(rec.size),
+            status
+        );
+    }
+
+    println!();
+    println!("Directory: {}", recording::recordings_dir(&name)?.display());
+
+    Ok(())
+}
+
+/// Format seconds as H:MM:SS
+fn format_duration(seconds: f64) -> String {
+    let total = seconds.round() as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        total / 3600,
+        (total % 3600) / 60,
+        total % 60
+    )
+}
+
+/// Format a byte count with a binary unit suffix
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
@@ -0,0 +1,34 @@
+use anyhow::{bail, Result};
+
+use crate::recording;
+
+pub fn run(name: String, id: Option<String>, speed: f64, idle_limit: Option<f64>) -> Result<()> {
+    // Convert any captures left behind by an interrupted attach
+    recording::finalize_pending(&name)?;
+
+    let recordings: Vec<_> = recording::list_recordings(&name)?
+        .into_iter()
+        .filter(|rec| rec.finished)
+        .collect();
+
+    let selected = match &id {
+        Some(id) => recordings.iter().find(|rec| &rec.id == id),
+        None => recordings.last(),
+    };
+
+    let rec = match selected {
+        Some(rec) => rec,
+        None => match id {
+            Some(id) => bail!(
+                "Recording '{}' not found for session '{}'\nList recordings: klotho recordings ls {}",
+                id,
+                name,
+                name
+            ),
+            None => bail!("No recordings found for session '{}'", name),
+        },
+    };
+
+    eprintln!("Replaying recording {} of session '{}'...", rec.id, name);
+    recording::replay(&rec.path, speed, idle_limit)
+}
@@ -1,21 +1,57 @@
 use anyhow::{bail, Result};
+use owo_colors::OwoColorize;
+use std::collections::HashMap;
 use std::io::{self, Write};
 use crate::container::{
-    container_status, detect_runtime, find_container, remove_container, ContainerStatus,
+    detect_runtime, get_container_labels, remove_container, ContainerStatus, Runtime,
 };
+use crate::git;
+use crate::selection::{self, Selector, Session};
+use crate::volume;
+use crate::worktree::Worktree;
 
-pub fn run(name: String, force: bool, runtime_override: Option<&str>) -> Result<()> {
+pub fn run(selector: Selector, force: bool, runtime_override: Option<&str>) -> Result<()> {
     let runtime = detect_runtime(runtime_override)?;
 
-    // Find container by session name
-    let container_name = match find_container(runtime, &name)? {
-        Some(name) => name,
-        None => bail!("Session '{}' not found", name),
-    };
+    // Find containers by session name, pattern or filter
+    let sessions = selection::select(runtime, &selector)?;
+
+    if selector.is_single() && sessions.len() == 1 {
+        return remove_single(runtime, &sessions[0], force);
+    }
+
+    // Running sessions are never removed in bulk
+    let (running, sessions): (Vec<Session>, Vec<Session>) = sessions
+        .into_iter()
+        .partition(|s| s.status == ContainerStatus::Running);
+    for session in &running {
+        println!(
+            "Skipping running session '{}' (stop it first)",
+            session.name
+        );
+    }
+
+    if sessions.is_empty() {
+        println!("No stopped sessions matched.");
+        return Ok(());
+    }
+    if !force && !selection::confirm("remove", &selection::describe(&sessions))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    for session in &sessions {
+        remove_session(runtime, session, force)?;
+    }
+
+    Ok(())
+}
+
+fn remove_single(runtime: Runtime, session: &Session, force: bool) -> Result<()> {
+    let name = &session.name;
 
     // Check if running
-    let status = container_status(runtime, &container_name)?;
-    if status == ContainerStatus::Running {
+    if session.status == ContainerStatus::Running {
         bail!(
             "Cannot remove running session '{}'\nStop it first: klotho stop {}",
             name,
@@ -38,9 +74,120 @@ pub fn run(name: String, force: bool, runtime_override: Option<&str>) -> Result<
         }
     }
 
+    remove_session(runtime, session, force)
+}
+
+/// Remove a stopped session's container and offer to remove its worktree
+pub(crate) fn remove_session(runtime: Runtime, session: &Session, force: bool) -> Result<()> {
+    // Read labels before the container (and its worktree link) is gone
+    let labels = get_container_labels(runtime, &session.container)?;
+
     // Remove container
-    remove_container(runtime, &container_name)?;
+    remove_container(runtime, &session.container)?;
+
+    println!("Removed: {}", session.name);
+
+    if let Some(worktree) = Worktree::from_labels(&labels) {
+        remove_worktree(&worktree, force)?;
+    }
+
+    remove_home_volumes(runtime, session, force)
+}
+
+/// Clean up after an ephemeral session whose container the runtime removed on stop
+///
+/// `labels` must be read before the container is stopped. `force` removes the
+/// worktree without asking, as `remove_session` does.
+pub(crate) fn cleanup_ephemeral(
+    runtime: Runtime,
+    labels: &HashMap<String, String>,
+    agent: &str,
+    session_name: &str,
+    force: bool,
+) -> Result<()> {
+    println!(
+        "{} Removed ephemeral session '{}'",
+        "✓".green(),
+        session_name
+    );
+
+    if let Some(worktree) = Worktree::from_labels(labels) {
+        remove_worktree(&worktree, force)?;
+    }
+    for home_volume in volume::session_volumes(runtime, agent, session_name)? {
+        volume::remove(runtime, &home_volume.name)?;
+    }
+    Ok(())
+}
+
+/// Offer to delete a session's home volumes (shared agent volumes are kept)
+fn remove_home_volumes(runtime: Runtime, session: &Session, force: bool) -> Result<()> {
+    let volumes = volume::session_volumes(runtime, &session.agent, &session.name)?;
+    if volumes.is_empty() {
+        return Ok(());
+    }
+
+    // Confirm unless --force
+    if !force {
+        let items: Vec<String> = volumes
+            .iter()
+            .map(|home_volume| format!("{} (/home/agent/{})", home_volume.name, home_volume.path))
+            .collect();
+        if !selection::confirm_items("delete", "home volume", &items)? {
+            println!("Kept home volumes (remove later with: klotho volumes rm)");
+            return Ok(());
+        }
+    }
+
+    for home_volume in &volumes {
+        volume::remove(runtime, &home_volume.name)?;
+        println!("Removed volume: {}", home_volume.name);
+    }
+    Ok(())
+}
+
+/// Offer to remove a session's worktree, keeping it if it holds unmerged work
+pub(crate) fn remove_worktree(worktree: &Worktree, force: bool) -> Result<()> {
+    if !worktree.path.exists() {
+        return Ok(());
+    }
+
+    let unmerged = worktree.unmerged_commits()?;
+    if unmerged > 0 {
+        println!(
+            "Kept worktree {} (branch '{}' has {} unmerged commit{})",
+            worktree.path.display(),
+            worktree.branch,
+            unmerged,
+            if unmerged == 1 { "" } else { "s" }
+        );
+        return Ok(());
+    }
+
+    if git::is_dirty(&worktree.path)? {
+        println!(
+            "Kept worktree {} (it has uncommitted changes)",
+            worktree.path.display()
+        );
+        return Ok(());
+    }
+
+    // Confirm unless --force
+    if !force {
+        print!("Remove worktree {}? [Y/n] ", worktree.path.display());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let answer = input.trim().to_lowercase();
+
+        if answer == "n" || answer == "no" {
+            println!("Kept worktree {}", worktree.path.display());
+            return Ok(());
+        }
+    }
 
-    println!("Removed: {}", name);
+    worktree.remove()?;
+    println!("Removed worktree: {}", worktree.path.display());
     Ok(())
 }
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use owo_colors::OwoColorize;
+use std::path::PathBuf;
+
+use crate::container::{container_status, detect_runtime, find_container, ContainerStatus};
+use crate::snapshot;
+
+pub fn run(name: String, label: String, force: bool, runtime_override: Option<&str>) -> Result<()> {
+    let snap = snapshot::load(&name, &label)?;
+
+    // Rolling back under a running agent races with its writes
+    let runtime = detect_runtime(runtime_override)?;
+    if let Some(container_name) = find_container(runtime, &name)? {
+        if container_status(runtime, &container_name)? == ContainerStatus::Running {
+            eprintln!(
+                "{} Session '{}' is running; the agent may keep writing during rollback",
+                "!".yellow(),
+                name
+            );
+        }
+    }
+
+    // Confirm unless --force
+    if !force {
+        println!("Rolling back to snapshot '{}' will overwrite:", label);
+        for dir in &snap.dirs {
+            println!("  {}", dir.path());
+        }
+
+        let confirmed = dialoguer::Confirm::new()
+            .with_prompt("Continue?")
+            .default(false)
+            .interact()?;
+
+        if !confirmed {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    // Keep the current state so a rollback can itself be undone
+    let dirs: Vec<PathBuf> = snap.dirs.iter().map(|d| PathBuf::from(d.path())).collect();
+    let safety = snapshot::create(&name, &snapshot::timestamped_label("pre-rollback-"), &dirs)?;
+
+    snapshot::restore(&snap)?;
+
+    println!(
+        "{} Rolled back session '{}' to '{}'",
+        "✓".green(),
+        name,
+        label.bold()
+    );
+    println!("  Undo with: klotho rollback {} {}", name, safety.label);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use anyhow::{bail, Result};
+use owo_colors::OwoColorize;
+
+use crate::container::detect_runtime;
+use crate::image::{image_id, image_ref, list_images, resolve_tag, tag_image};
+use crate::time::{format_compact, unix_now};
+
+/// Retag an earlier versioned build of an agent's image as latest
+pub fn run(agent: &str, tag: &str, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    if tag == "latest" {
+        bail!("TAG must be a versioned build, e.g. one listed by: klotho images");
+    }
+
+    let target = resolve_tag(runtime, agent, tag)?;
+    let target_id = image_id(runtime, &target)?;
+    let latest = image_ref(agent, "latest");
+
+    // Find the version the current latest was built as, so it stays reachable
+    let previous = match image_id(runtime, &latest) {
+        Ok(id) if id == target_id => {
+            println!("{} already points at {}", latest, tag);
+            return Ok(());
+        }
+        Ok(id) => {
+            let prefix = format!("klotho-{}:", agent);
+            let images = list_images(runtime)?;
+            let versioned = images.iter().find_map(|image| {
+                let version = image.name.as_ref()?.strip_prefix(&prefix)?;
+                (image.id == id && version != "latest").then(|| version.to_string())
+            });
+
+            match versioned {
+                Some(version) => Some(version),
+                None => {
+                    // Built before versioned tags: tag it by its build time
+                    let created = images
+                        .iter()
+                        .find(|image| image.id == id)
+                        .and_then(|image| image.created)
+                        .unwrap_or_else(unix_now);
+                    let version = format_compact(created);
+                    tag_image(runtime, &latest, &image_ref(agent, &version))?;
+                    println!("Tagged previous latest as {}", image_ref(agent, &version));
+                    Some(version)
+                }
+            }
+        }
+        Err(_) => None,
+    };
+
+    tag_image(runtime, &target, &latest)?;
+
+    println!("{} {} → {}", "✓".green(), latest.cyan(), tag.bold());
+    println!(
+        "New sessions use this build; existing sessions keep the image they were created from."
+    );
+    if let Some(previous) = previous {
+        println!("Undo with: klotho rollback-image {} {}", agent, previous);
+    }
+
+    Ok(())
+}
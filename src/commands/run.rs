@@ -0,0 +1,167 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::commands::start::{ensure_image_built, select_agent_interactive, shared_mount_args};
+use crate::config::{get_state_home, load_agent_config};
+use crate::container::{detect_runtime, get_image_name};
+use crate::git;
+use crate::headless::{self, CONTAINER_TASK_DIR, EXIT_CODE_FILE};
+use crate::time::unix_now;
+
+/// Summary written to result.toml in the bundle
+#[derive(Serialize)]
+struct RunResult {
+    agent: String,
+    project: String,
+    image: String,
+    started: u64,
+    finished: u64,
+    exit_code: i32,
+}
+
+/// Run an agent non-interactively on a prompt in an ephemeral container
+pub fn run(
+    agent: Option<String>,
+    prompt: Option<String>,
+    prompt_file: Option<String>,
+    output: Option<String>,
+    path: Option<String>,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let prompt = headless::read_prompt(prompt, prompt_file)?;
+
+    // Determine agent (interactive selection if None)
+    let agent = match agent {
+        Some(a) => a,
+        None => select_agent_interactive()?,
+    };
+    let (config, _is_legacy) = load_agent_config(&agent)?;
+    let env_args = headless::exec_env_args(&config, &prompt)?;
+
+    ensure_image_built(runtime, &agent)?;
+
+    let project = match path {
+        Some(p) => PathBuf::from(p)
+            .canonicalize()
+            .context("Failed to resolve project path")?,
+        None => env::current_dir().context("Failed to get current directory")?,
+    };
+
+    // Result bundle directory (also the task directory the container writes into)
+    let started = unix_now();
+    let bundle = match output {
+        Some(dir) => PathBuf::from(dir),
+        None => get_state_home()
+            .join("runs")
+            .join(format!("run-{}-{}", agent, started)),
+    };
+    fs::create_dir_all(&bundle).context("failed to create result directory")?;
+    let bundle = bundle.canonicalize()?;
+    fs::write(bundle.join("prompt.md"), &prompt).context("failed to write prompt")?;
+
+    // Capture the work tree first so the diff only shows the agent's changes.
+    // A project inside a repository captures the whole repository; the diff
+    // is scoped to the project afterwards.
+    let is_repo = git::toplevel(&project).is_ok();
+    let before = if is_repo {
+        Some(git::write_worktree_tree(&project)?)
+    } else {
+        None
+    };
+
+    let mut mount_args = vec![
+        "-v".to_string(),
+        format!("{}:/workspace:Z", project.display()),
+        "-v".to_string(),
+        format!("{}:{}:Z", bundle.display(), CONTAINER_TASK_DIR),
+    ];
+    mount_args.extend(shared_mount_args(runtime, Vec::new())?);
+
+    let image_name = get_image_name(runtime, &agent)?;
+    let script = headless::task_script(&config, None);
+
+    eprintln!(
+        "{} Running {} on {}",
+        "→".cyan(),
+        agent.bold(),
+        project.display()
+    );
+
+    // --rm removes the container however the run ends, including Ctrl+C
+    let status = runtime
+        .command()
+        .args(["run", "--rm", "--label=klotho.run=true", "--userns=keep-id"])
+        .args(["--workdir", "/workspace"])
+        .args(&env_args)
+        .args(&mount_args)
+        .arg(&image_name)
+        .args(["bash", "-c", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status()
+        .context("Failed to run container")?;
+
+    // Prefer the code the task recorded; the runtime may add its own on failure
+    let exit_code = fs::read_to_string(bundle.join(EXIT_CODE_FILE))
+        .ok()
+        .and_then(|c| c.trim().parse().ok())
+        .unwrap_or_else(|| status.code().unwrap_or(-1));
+    fs::write(bundle.join(EXIT_CODE_FILE), format!("{}\n", exit_code))?;
+
+    if let Some(before) = before {
+        write_diff(&project, &before, &bundle.join("diff.patch"))?;
+    }
+
+    let result = RunResult {
+        agent: agent.clone(),
+        project: project.display().to_string(),
+        image: image_name,
+        started,
+        finished: unix_now(),
+        exit_code,
+    };
+    fs::write(
+        bundle.join("result.toml"),
+        toml::to_string(&result).context("failed to serialize result")?,
+    )
+    .context("failed to write result")?;
+
+    eprintln!();
+    eprintln!("Result bundle: {}", bundle.display());
+
+    if exit_code != 0 {
+        bail!("{} exited with code {}", agent, exit_code);
+    }
+
+    eprintln!("{} {} finished", "✓".green(), agent.bold());
+    Ok(())
+}
+
+/// Write the diff between a captured tree and the current work tree
+///
+/// Only changes under the project are included, with paths relative to it.
+fn write_diff(project: &Path, before: &str, dest: &Path) -> Result<()> {
+    let after = git::write_worktree_tree(project)?;
+
+    // Not git::git - trimming the output would corrupt trailing context lines
+    let output = git::command(project)
+        .args(["diff", "--binary", "--relative", before, &after])
+        .output()
+        .context("failed to run git diff")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("git diff failed: {}", stderr.trim());
+    }
+
+    fs::write(dest, &output.stdout).context("failed to write diff")?;
+    Ok(())
+}
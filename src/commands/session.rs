@@ -0,0 +1,209 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+
+use crate::bundle::{self, Bundle, SessionManifest, SessionVolume, LAYOUT, SESSION_KIND};
+use crate::commands::export::write_agent_config;
+use crate::commands::import::install_config;
+use crate::commands::start::{self, ensure_image_built, CreateOptions};
+use crate::config::load_agent_config;
+use crate::container::{
+    container_status, detect_runtime, find_container, get_container_labels, get_image_name,
+    get_project_dirs, zellij_session_active, ContainerStatus,
+};
+use crate::image::{image_ref, resolve_tag, IMAGE_ID_LABEL, IMAGE_TAG_LABEL};
+use crate::recording::RECORD_LABEL;
+use crate::selection::session_agent;
+use crate::time::unix_now;
+use crate::volume::{self, HomeVolume};
+
+/// Save a session's settings, home volumes, agent config and Zellij layout to a file
+pub fn run_export(
+    name: &str,
+    output: Option<String>,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let container_name = match find_container(runtime, name)? {
+        Some(container_name) => container_name,
+        None => bail!("Session '{}' not found", name),
+    };
+    let labels = get_container_labels(runtime, &container_name)?;
+    let agent = session_agent(&labels, &container_name, name)?;
+    let (config, _) = load_agent_config(&agent)?;
+    let output = PathBuf::from(output.unwrap_or_else(|| format!("klotho-session-{}.tar", name)));
+
+    let bundle = Bundle::new()?;
+
+    // Volumes are read with the session's own image, which is known to have tar
+    let image = match labels.get(IMAGE_ID_LABEL) {
+        Some(id) => id.clone(),
+        None => image_ref(&agent, "latest"),
+    };
+    let mut volumes = Vec::new();
+    for home_volume in volume::declared(&config, name)? {
+        if !volume::exists(runtime, &home_volume.name)? {
+            continue;
+        }
+        let archive = format!("volumes/{}.tar", volume::path_slug(&home_volume.path));
+        eprintln!(
+            "{} Saving /home/agent/{}",
+            "→".cyan(),
+            home_volume.path.cyan()
+        );
+        std::fs::create_dir_all(bundle.path("volumes"))
+            .context("failed to create bundle directory")?;
+        volume::save(runtime, &home_volume.name, &image, &bundle.path(&archive))?;
+        volumes.push(SessionVolume {
+            path: home_volume.path.clone(),
+            shared: home_volume.session.is_none(),
+            archive,
+        });
+    }
+
+    // The layout can only be dumped from a running Zellij session
+    let running = container_status(runtime, &container_name)? == ContainerStatus::Running;
+    if running && zellij_session_active(runtime, &container_name, name)? {
+        let dump = runtime
+            .command()
+            .args(["exec", &container_name, "zellij", "--session", name])
+            .args(["action", "dump-layout"])
+            .output()
+            .context("failed to dump Zellij layout")?;
+        if dump.status.success() {
+            bundle.write(LAYOUT, &dump.stdout)?;
+        } else {
+            let stderr = String::from_utf8_lossy(&dump.stderr);
+            eprintln!("warning: failed to dump Zellij layout: {}", stderr.trim());
+        }
+    } else {
+        eprintln!(
+            "{} Zellij session '{}' isn't running, exporting without its layout",
+            "!".yellow(),
+            name
+        );
+    }
+
+    write_agent_config(&bundle, &agent)?;
+
+    bundle.write_manifest(&SessionManifest {
+        kind: SESSION_KIND.to_string(),
+        klotho_version: env!("CARGO_PKG_VERSION").to_string(),
+        name: name.to_string(),
+        agent: agent.clone(),
+        exported: unix_now(),
+        image_tag: labels.get(IMAGE_TAG_LABEL).cloned(),
+        record: labels.get(RECORD_LABEL).map(String::as_str) == Some("true"),
+        projects: get_project_dirs(runtime, &container_name)?,
+        volumes,
+    })?;
+    bundle.pack(&output)?;
+
+    eprintln!(
+        "{} Exported session '{}' ({}) → {}",
+        "✓".green(),
+        name.bold(),
+        agent,
+        output.display()
+    );
+    eprintln!(
+        "Recreate it elsewhere with: klotho session import {} [PATH]",
+        output.display()
+    );
+    Ok(())
+}
+
+/// Recreate a session exported with `klotho session export` on the given project paths
+pub fn run_import(
+    file: &str,
+    name: Option<String>,
+    paths: Vec<String>,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+    let file = Path::new(file);
+
+    let bundle = Bundle::open(file)?;
+    let manifest: SessionManifest = bundle.read_manifest()?;
+    if manifest.kind != SESSION_KIND {
+        bail!(
+            "{} holds a {} bundle, not a session (see: klotho import)",
+            file.display(),
+            manifest.kind
+        );
+    }
+    bundle::validate_name("agent", &manifest.agent)?;
+    let agent = &manifest.agent;
+    let name = name.unwrap_or_else(|| manifest.name.clone());
+    bundle::validate_name("session name", &name)?;
+    for entry in &manifest.volumes {
+        entry.validate()?;
+    }
+
+    if find_container(runtime, &name)?.is_some() {
+        bail!(
+            "Session '{}' already exists, import under another name with --name",
+            name
+        );
+    }
+
+    install_config(&bundle, agent)?;
+    ensure_image_built(runtime, agent)?;
+
+    // A pinned build only carries over if this machine has it
+    let image_tag = manifest
+        .image_tag
+        .filter(|tag| match resolve_tag(runtime, agent, tag) {
+            Ok(_) => true,
+            Err(_) => {
+                eprintln!(
+                    "{} Build '{}' isn't available here, using latest",
+                    "!".yellow(),
+                    tag
+                );
+                false
+            }
+        });
+
+    // Restore volumes before start creates the container, which then mounts them
+    let image = get_image_name(runtime, agent)?;
+    for entry in &manifest.volumes {
+        let session = if entry.shared {
+            None
+        } else {
+            Some(name.as_str())
+        };
+        let home_volume = HomeVolume::new(agent, session, &entry.path);
+        if volume::exists(runtime, &home_volume.name)? {
+            eprintln!(
+                "{} Keeping existing volume {} for /home/agent/{}",
+                "!".yellow(),
+                home_volume.name,
+                entry.path
+            );
+            continue;
+        }
+        eprintln!("{} Restoring /home/agent/{}", "→".cyan(), entry.path.cyan());
+        volume::ensure(runtime, &home_volume)?;
+        volume::restore(
+            runtime,
+            &home_volume.name,
+            &image,
+            &bundle.path(&entry.archive),
+        )?;
+    }
+
+    if !manifest.projects.is_empty() {
+        eprintln!("Exported from {}", manifest.projects.join(", ").dimmed());
+    }
+
+    let layout = bundle.read(LAYOUT)?.map(|_| bundle.path(LAYOUT));
+    let options = CreateOptions {
+        record: manifest.record,
+        image_tag,
+        layout,
+        ..Default::default()
+    };
+    start::run(Some(agent.clone()), name, paths, options, runtime_override)
+}
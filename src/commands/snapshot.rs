@@ -0,0 +1,104 @@
+use anyhow::{bail, Result};
+use owo_colors::OwoColorize;
+use std::path::PathBuf;
+
+use crate::container::{detect_runtime, find_container, get_project_dirs, Runtime};
+use crate::snapshot;
+use crate::time::{format_age, unix_now};
+
+pub fn run(
+    name: String,
+    label: Option<String>,
+    list: bool,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    if list {
+        return list_snapshots(&name);
+    }
+
+    let runtime = detect_runtime(runtime_override)?;
+
+    // Find container by session name
+    let container_name = match find_container(runtime, &name)? {
+        Some(name) => name,
+        None => bail!("Session '{}' not found", name),
+    };
+
+    let dirs: Vec<PathBuf> = get_project_dirs(runtime, &container_name)?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+
+    if dirs.is_empty() {
+        bail!("Session '{}' has no mounted project directories", name);
+    }
+
+    let label = label.unwrap_or_else(|| snapshot::timestamped_label(""));
+    let snap = snapshot::create(&name, &label, &dirs)?;
+
+    println!(
+        "{} Snapshot '{}' of session '{}'",
+        "✓".green(),
+        snap.label.bold(),
+        name
+    );
+    for dir in &snap.dirs {
+        println!("  {}", dir.path());
+    }
+
+    Ok(())
+}
+
+/// Print snapshots recorded for a session
+fn list_snapshots(name: &str) -> Result<()> {
+    let snapshots = snapshot::list(name)?;
+
+    if snapshots.is_empty() {
+        println!("No snapshots found for session '{}'.", name);
+        return Ok(());
+    }
+
+    println!("{:<30} {:<14} {:<6}", "LABEL", "CREATED", "DIRS");
+    println!("{}", "-".repeat(52));
+
+    let now = unix_now();
+    for snap in snapshots {
+        println!(
+            "{:<30} {:<14} {:<6}",
+            snap.label,
+            format!("{} ago", format_age(now.saturating_sub(snap.created))),
+            snap.dirs.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Take an automatic snapshot if KLOTHO_AUTO_SNAPSHOT is set
+///
+/// Failures are reported but never block starting a session.
+pub(crate) fn auto_snapshot_if_enabled(name: &str, dirs: &[PathBuf]) {
+    if !snapshot::auto_snapshot_enabled() || dirs.is_empty() {
+        return;
+    }
+
+    match snapshot::auto_snapshot(name, dirs) {
+        Ok(snap) => println!("Snapshot '{}' taken of session '{}'", snap.label, name),
+        Err(e) => eprintln!("warning: automatic snapshot failed: {}", e),
+    }
+}
+
+/// Take an automatic snapshot of a session's mounted project directories
+pub(crate) fn auto_snapshot_container(runtime: Runtime, name: &str, container_name: &str) {
+    if !snapshot::auto_snapshot_enabled() {
+        return;
+    }
+
+    match get_project_dirs(runtime, container_name) {
+        Ok(dirs) => {
+            let dirs: Vec<PathBuf> = dirs.into_iter().map(PathBuf::from).collect();
+            auto_snapshot_if_enabled(name, &dirs);
+        }
+        Err(e) => eprintln!("warning: automatic snapshot failed: {}", e),
+    }
+}
@@ -2,23 +2,61 @@ use anyhow::{bail, Context, Result};
 use dialoguer::Select;
 use owo_colors::OwoColorize;
 use std::env;
-use std::path::PathBuf;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use crate::agent::{self, AgentConfig};
-use crate::commands::build;
+use crate::cache;
+use crate::commands::{build, rm, snapshot};
 use crate::config::load_agent_config;
 use crate::container::{
-    container_status, detect_runtime, find_container, get_image_name, image_exists,
-    start_container, ContainerStatus, Runtime,
+    container_status, detect_runtime, find_container, get_container_labels, get_image_name,
+    image_exists, remove_container, start_container, stop_container, zellij_session_active,
+    zellij_session_exists, ContainerStatus, Runtime, EPHEMERAL_LABEL, LAYOUT_LABEL, MOUNTS_LABEL,
 };
+use crate::customization::Customization;
+use crate::derived;
+use crate::devcontainer::{self, DEVCONTAINER_LABEL};
+use crate::image::{self, image_age, image_ref, AGENT_LABEL, IMAGE_ID_LABEL, IMAGE_TAG_LABEL};
+use crate::recording::{self, CONTAINER_RECORDINGS_DIR, RECORD_LABEL};
 use crate::resources;
+use crate::time::format_age;
+use crate::volume;
+use crate::worktree;
+
+/// Options that only take effect when a new session container is created
+#[derive(Debug, Default)]
+pub struct CreateOptions {
+    /// Directories to mount at the same path for symlink resolution
+    pub linked_dirs: Vec<String>,
+    /// Record the terminal stream of every attach
+    pub record: bool,
+    /// Mount a git worktree on this branch instead of the project
+    pub worktree: Option<String>,
+    /// Remove the container when the Zellij session exits or the container stops
+    pub ephemeral: bool,
+    /// Create the container from this versioned build instead of latest
+    pub image_tag: Option<String>,
+    /// Zellij layout the session's Zellij session starts from (`klotho session import`)
+    pub layout: Option<PathBuf>,
+}
+
+/// Where a session's Zellij layout is copied inside the container
+const CONTAINER_LAYOUT: &str = "/home/agent/.klotho/layout.kdl";
+
+impl CreateOptions {
+    /// Whether any creation-only flag was given explicitly
+    fn any_set(&self) -> bool {
+        self.record || self.worktree.is_some() || self.ephemeral || self.image_tag.is_some()
+    }
+}
 
 pub fn run(
     agent: Option<String>,
     name: String,
-    linked_dirs: Vec<String>,
     paths: Vec<String>,
+    options: CreateOptions,
     runtime_override: Option<&str>,
 ) -> Result<()> {
     // Detect runtime
@@ -33,15 +71,19 @@ pub fn run(
     // Load agent config
     let (config, _is_legacy) = load_agent_config(&agent)?;
 
-    // Ensure image is built
-    ensure_image_built(runtime, &agent)?;
-
     // Check for existing container (new naming then legacy)
     let container_name_new = format!("klotho-session-{}-{}", agent, name);
 
     let existing_container = find_container(runtime, &name)?;
 
     if let Some(container_name) = existing_container {
+        if options.any_set() {
+            eprintln!(
+                "warning: --record, --worktree, --rm and --image-tag only apply when a session is created; '{}' already exists",
+                name
+            );
+        }
+
         // Container exists - check if running
         let status = container_status(runtime, &container_name)?;
 
@@ -52,6 +94,7 @@ pub fn run(
             }
             ContainerStatus::Stopped => {
                 println!("Starting stopped session '{}'...", name);
+                snapshot::auto_snapshot_container(runtime, &name, &container_name);
                 start_container(runtime, &container_name)?;
                 std::thread::sleep(std::time::Duration::from_secs(1));
                 return attach_zellij(runtime, &container_name, &name, &config);
@@ -62,11 +105,18 @@ pub fn run(
         }
     }
 
-    // Create new container
+    // Create new container, rebuilding the image first if its inputs changed
+    ensure_image_built(runtime, &agent)?;
     println!("Creating new session '{}'...", name);
 
+    // Get image name: a pinned build, else latest (prefer new, fallback to legacy)
+    let image_name = match &options.image_tag {
+        Some(tag) => image::resolve_tag(runtime, &agent, tag)?,
+        None => get_image_name(runtime, &agent)?,
+    };
+
     // Resolve paths (default to cwd if empty)
-    let resolved_paths = if paths.is_empty() {
+    let mut resolved_paths = if paths.is_empty() {
         vec![env::current_dir().context("Failed to get current directory")?]
     } else {
         paths
@@ -76,8 +126,69 @@ pub fn run(
             .context("Failed to resolve project path")?
     };
 
-    // Build mount arguments
+    // A devcontainer.json in the project: install the agent on its image instead
+    let devcontainer = devcontainer::find(&resolved_paths[0])?;
+    let image_name = match &devcontainer {
+        Some(dev) => {
+            if options.image_tag.is_some() {
+                bail!(
+                    "--image-tag can't be used with {}: the agent is built on the devcontainer image",
+                    dev.path.display()
+                );
+            }
+            let base = dev.base_image(runtime)?;
+            build::build_on_base(runtime, &agent, &base)?
+        }
+        None => image_name,
+    };
+
+    let image_name = project_image(runtime, &agent, &image_name, &resolved_paths[0])?;
+
+    // Build mount and label arguments
     let mut mount_args = Vec::new();
+    let mut label_args = vec![format!("--label={}={}", AGENT_LABEL, agent)];
+    if let Some(tag) = &options.image_tag {
+        label_args.push(format!("--label={}={}", IMAGE_TAG_LABEL, tag));
+    }
+    if let Some(dev) = &devcontainer {
+        label_args.push(format!(
+            "--label={}={}",
+            DEVCONTAINER_LABEL,
+            dev.path.display()
+        ));
+    }
+    if options.layout.is_some() {
+        label_args.push(format!("--label={}={}", LAYOUT_LABEL, CONTAINER_LAYOUT));
+    }
+
+    // Worktree mode: mount a klotho-managed worktree instead of the project itself
+    let worktree = match &options.worktree {
+        Some(branch) => {
+            if resolved_paths.len() != 1 {
+                bail!("--worktree requires exactly one project path");
+            }
+            let wt = worktree::create(&resolved_paths[0], &name, branch)?;
+            println!(
+                "Created worktree for branch '{}' at {}",
+                wt.branch.bold(),
+                wt.path.display()
+            );
+
+            // The worktree's .git file refers to the repository by absolute path.
+            // Other worktree sessions and the host share it, so it gets the shared
+            // SELinux label (:z); only the worktree itself is private (:Z).
+            let git_dir = wt.git_common_dir()?;
+            mount_args.push("-v".to_string());
+            mount_args.push(format!("{}:{}:z", git_dir.display(), git_dir.display()));
+
+            label_args.extend(wt.labels());
+            resolved_paths = vec![wt.path.clone()];
+            Some(wt)
+        }
+        None => None,
+    };
+
+    snapshot::auto_snapshot_if_enabled(&name, &resolved_paths);
 
     // Project paths with :Z for SELinux
     for (i, path) in resolved_paths.iter().enumerate() {
@@ -90,6 +201,107 @@ pub fn run(
         mount_args.push(format!("{}:{}:Z", path.display(), mount_point));
     }
 
+    // Linked directories, extra mounts and agent config from the host
+    mount_args.extend(shared_mount_args(runtime, options.linked_dirs)?);
+
+    // Home volumes: declared paths under /home/agent that outlive the container
+    for home_volume in volume::declared(&config, &name)? {
+        if cache::covers(&home_volume.path) {
+            eprintln!(
+                "warning: /home/agent/{} is a shared cache (klotho cache ls), skipping home volume",
+                home_volume.path
+            );
+            continue;
+        }
+        volume::ensure(runtime, &home_volume)?;
+        mount_args.push("-v".to_string());
+        mount_args.push(home_volume.mount_spec(runtime));
+    }
+
+    // Recording: mount the host recordings directory for script(1) to write into
+    if options.record {
+        let recordings_dir = recording::recordings_dir(&name)?;
+        std::fs::create_dir_all(&recordings_dir)
+            .context("failed to create recordings directory")?;
+        mount_args.push("-v".to_string());
+        mount_args.push(format!(
+            "{}:{}:Z",
+            recordings_dir.display(),
+            CONTAINER_RECORDINGS_DIR
+        ));
+        label_args.push(format!("--label={}=true", RECORD_LABEL));
+    }
+
+    // devcontainer.json containerEnv, forwardPorts and mounts
+    if let Some(dev) = &devcontainer {
+        mount_args.extend(dev.run_args());
+    }
+
+    // Get working directory (first mount point)
+    let workdir = if resolved_paths.len() == 1 {
+        "/workspace".to_string()
+    } else {
+        "/workspace1".to_string()
+    };
+
+    let created = create_container(
+        runtime,
+        &container_name_new,
+        &image_name,
+        &workdir,
+        &label_args,
+        &mount_args,
+        options.ephemeral,
+    );
+
+    if created.is_err() {
+        // Don't leave an orphaned worktree behind
+        if let Some(wt) = &worktree {
+            let _ = wt.remove();
+        }
+    }
+    created?;
+
+    if let Some(layout) = &options.layout {
+        copy_layout(runtime, &container_name_new, layout)?;
+    }
+
+    // postCreateCommand runs once, when the container is first created. A failed
+    // run takes the container with it, so the next start creates it and runs it again
+    if let Some(dev) = &devcontainer {
+        if let Err(e) = dev.run_post_create(runtime, &container_name_new, &workdir) {
+            let _ = stop_container(runtime, &container_name_new);
+            if !options.ephemeral {
+                let _ = remove_container(runtime, &container_name_new);
+            }
+            if let Some(wt) = &worktree {
+                let _ = wt.remove();
+            }
+            return Err(e.context(format!("session '{}' was not created", name)));
+        }
+    }
+
+    println!(
+        "{} Created session '{}' → {}",
+        "✓".green(),
+        name.bold(),
+        container_name_new.cyan()
+    );
+
+    // Give container a moment to start
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    // Attach to zellij
+    attach_zellij(runtime, &container_name_new, &name, &config)
+}
+
+/// Mount arguments shared by every session container
+///
+/// Covers linked directories (KLOTHO_LINKED_DIRS and --linked-dir), KLOTHO_MOUNTS,
+/// the host's agent configuration and the shared package caches.
+pub(crate) fn shared_mount_args(runtime: Runtime, linked_dirs: Vec<String>) -> Result<Vec<String>> {
+    let mut mount_args = Vec::new();
+
     // KLOTHO_LINKED_DIRS: directories mounted at same path for symlink resolution
     let mut all_linked_dirs = Vec::new();
 
@@ -166,29 +378,57 @@ pub fn run(
         mount_args.push(format!("{}:/home/agent/.claude.json:Z", claude_json));
     }
 
-    // Get image name (prefer new, fallback to legacy)
-    let image_name = get_image_name(runtime, &agent)?;
+    // Package caches shared by all sessions (KLOTHO_CACHES)
+    mount_args.extend(cache::mount_args(runtime)?);
 
-    // Get working directory (first mount point)
-    let workdir = if resolved_paths.len() == 1 {
-        "/workspace".to_string()
-    } else {
-        "/workspace1".to_string()
-    };
+    Ok(mount_args)
+}
 
+/// Create a detached session container that stays running for exec attachment
+pub(crate) fn create_container(
+    runtime: Runtime,
+    container_name: &str,
+    image_name: &str,
+    workdir: &str,
+    label_args: &[String],
+    run_args: &[String],
+    ephemeral: bool,
+) -> Result<()> {
     // Run podman run with all mounts
     // Use keep-alive loop so container stays running for exec attachment
     let mut cmd = runtime.command();
-    cmd.arg("run")
-        .arg("-d")
-        .arg("--name")
-        .arg(&container_name_new)
+    cmd.arg("run").arg("-d");
+
+    // Ephemeral sessions are removed by the runtime as soon as they stop
+    if ephemeral {
+        cmd.arg("--rm")
+            .arg(format!("--label={}=true", EPHEMERAL_LABEL));
+    }
+
+    // Record the exact build so the session can be traced back to it after rebuilds
+    let image_id = image::image_id(runtime, image_name)?;
+
+    // Record the mounts so `klotho upgrade` can recreate the container. Other
+    // flags come from devcontainer.json and are read from it again, so that
+    // containerEnv values never end up in a label.
+    let mount_specs: Vec<&str> = run_args
+        .chunks(2)
+        .filter(|pair| pair.len() == 2 && pair[0] == "-v")
+        .map(|pair| pair[1].as_str())
+        .collect();
+    let mount_specs = serde_json::to_string(&mount_specs).context("failed to record mounts")?;
+
+    cmd.arg("--name")
+        .arg(container_name)
         .arg("--label=klotho=true")
+        .arg(format!("--label={}={}", IMAGE_ID_LABEL, image_id))
+        .arg(format!("--label={}={}", MOUNTS_LABEL, mount_specs))
+        .args(label_args)
         .arg("--userns=keep-id")
         .arg("--workdir")
-        .arg(&workdir)
-        .args(&mount_args)
-        .arg(&image_name)
+        .arg(workdir)
+        .args(run_args)
+        .arg(image_name)
         .args(["bash", "-c", "trap 'exit 0' TERM; while :; do sleep 1; done"]);
 
     let output = cmd.output().context("Failed to create container")?;
@@ -198,22 +438,34 @@ pub fn run(
         bail!("Failed to create container: {}", stderr);
     }
 
-    println!(
-        "{} Created session '{}' → {}",
-        "✓".green(),
-        name.bold(),
-        container_name_new.cyan()
-    );
+    Ok(())
+}
 
-    // Give container a moment to start
-    std::thread::sleep(std::time::Duration::from_millis(500));
+/// Copy a Zellij layout into a new session container, owned by the agent user
+fn copy_layout(runtime: Runtime, container_name: &str, layout: &Path) -> Result<()> {
+    let file =
+        File::open(layout).with_context(|| format!("failed to open {}", layout.display()))?;
+    let output = runtime
+        .command()
+        .args(["exec", "-i", container_name, "sh", "-c"])
+        .arg(format!(
+            "mkdir -p \"$(dirname {0})\" && cat > {0}",
+            CONTAINER_LAYOUT
+        ))
+        .stdin(file)
+        .output()
+        .context("failed to copy Zellij layout")?;
 
-    // Attach to zellij
-    attach_zellij(runtime, &container_name_new, &name, &config)
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to copy Zellij layout: {}", stderr.trim());
+    }
+
+    Ok(())
 }
 
 /// Select agent interactively
-fn select_agent_interactive() -> Result<String> {
+pub(crate) fn select_agent_interactive() -> Result<String> {
     let available_agents = if resources::should_use_embedded() {
         resources::list_embedded_agents()
     } else {
@@ -237,9 +489,51 @@ fn select_agent_interactive() -> Result<String> {
     Ok(available_agents[selection].clone())
 }
 
+/// Apply a project's .klotho/config.conf customization and .klotho/Containerfile on
+/// top of an agent image, building derived images as needed
+///
+/// Returns `image` itself for projects with neither.
+pub(crate) fn project_image(
+    runtime: Runtime,
+    agent: &str,
+    image: &str,
+    project: &Path,
+) -> Result<String> {
+    let mut image = image.to_string();
+
+    if let Some(custom) = Customization::project(project)? {
+        // A project base image needs the agent installed on it, like a devcontainer's
+        if let Some(base) = &custom.base_image {
+            if !image.ends_with(":latest") {
+                bail!(
+                    "BUILD_BASE_IMAGE in the project's .klotho/config.conf can't be combined with --image-tag or a devcontainer.json"
+                );
+            }
+            image = build::build_on_base(runtime, agent, base)?;
+        }
+        image = derived::ensure_customized(runtime, agent, &image, project, &custom)?;
+    }
+
+    if let Some(file) = derived::find(project)? {
+        image = derived::ensure(runtime, agent, &image, &file)?;
+    }
+    Ok(image)
+}
+
 /// Ensure image is built, prompt to build if missing
-fn ensure_image_built(runtime: Runtime, agent: &str) -> Result<()> {
+pub(crate) fn ensure_image_built(runtime: Runtime, agent: &str) -> Result<()> {
     if image_exists(runtime, agent)? {
+        // Base image, packages, files or steps changed in a config: rebuild
+        if build::inputs_changed(runtime, agent)? {
+            eprintln!(
+                "{} Build customization for '{}' changed, rebuilding image",
+                "→".cyan(),
+                agent.bold()
+            );
+            build::run_build(runtime, agent, false)?;
+        } else {
+            warn_if_stale(runtime, agent)?;
+        }
         return Ok(());
     }
 
@@ -265,75 +559,84 @@ fn ensure_image_built(runtime: Runtime, agent: &str) -> Result<()> {
     Ok(())
 }
 
-/// Strip ANSI escape codes from a string
-fn strip_ansi_codes(s: &str) -> String {
-    // ANSI escape sequences follow pattern: ESC [ <params> m
-    // where ESC is \x1b, params are digits/semicolons
-    let mut result = String::with_capacity(s.len());
-    let mut chars = s.chars().peekable();
-
-    while let Some(ch) = chars.next() {
-        if ch == '\x1b' {
-            // Check if this is start of ANSI sequence
-            if chars.peek() == Some(&'[') {
-                chars.next(); // consume '['
-                // Skip until we hit 'm' (or end of string)
-                while let Some(ch) = chars.next() {
-                    if ch == 'm' {
-                        break;
-                    }
-                }
-                continue;
-            }
+/// Warn when an agent's latest image is older than its configured max age
+fn warn_if_stale(runtime: Runtime, agent: &str) -> Result<()> {
+    let (config, _) = load_agent_config(agent)?;
+    let Some(max_age) = config.max_image_age else {
+        return Ok(());
+    };
+
+    if let Some(age) = image_age(runtime, &image_ref(agent, "latest")) {
+        if age > max_age.as_secs() {
+            eprintln!(
+                "{} Image for '{}' is {} old (max {}), rebuild with: klotho rebuild {}",
+                "!".yellow(),
+                agent.bold(),
+                format_age(age),
+                format_age(max_age.as_secs()),
+                agent
+            );
         }
-        result.push(ch);
     }
-
-    result
+    Ok(())
 }
 
 /// Attach to zellij session in container
-fn attach_zellij(
+pub(crate) fn attach_zellij(
     runtime: Runtime,
     container_name: &str,
     session_name: &str,
     config: &AgentConfig,
 ) -> Result<()> {
     // Check if zellij session exists
-    let check = Command::new(runtime.as_str())
-        .args(["exec", container_name, "zellij", "list-sessions"])
-        .output()
-        .context("Failed to list zellij sessions")?;
-
-    let stdout = String::from_utf8_lossy(&check.stdout);
-    // Strip ANSI codes for comparison (regex pattern: \x1b\[[0-9;]*m)
-    let clean_output = strip_ansi_codes(&stdout);
-    let session_exists = clean_output
-        .lines()
-        .any(|line| line.trim().starts_with(session_name));
+    let session_exists = zellij_session_exists(runtime, container_name, session_name)?;
+    let labels = get_container_labels(runtime, container_name)?;
+    let ephemeral = labels.get(EPHEMERAL_LABEL).map(String::as_str) == Some("true");
 
     // Build the attach/create command
     let zellij_cmd = if session_exists {
         // Attach to existing session
-        format!(
-            "zellij attach '{}'; zellij list-sessions 2>/dev/null | sed 's/\\x1b\\[[0-9;]*m//g' | grep -q '^{} ' || exec {}",
-            session_name, session_name, config.shell
-        )
+        format!("zellij attach '{}'", session_name)
+    } else if let Some(layout) = labels.get(LAYOUT_LABEL) {
+        // Imported session: recreate the tabs and panes it was exported with
+        format!("zellij -s '{}' --layout '{}'", session_name, layout)
     } else {
         // Create new session with agent wrapper
+        format!("zellij -s '{}'", session_name)
+    };
+    // Drop to a shell when the session exits, unless the container goes with it
+    let zellij_cmd = if ephemeral {
+        zellij_cmd
+    } else {
         format!(
-            "zellij -s '{}'; zellij list-sessions 2>/dev/null | sed 's/\\x1b\\[[0-9;]*m//g' | grep -q '^{} ' || exec {}",
-            session_name, session_name, config.shell
+            "{}; zellij list-sessions 2>/dev/null | sed 's/\\x1b\\[[0-9;]*m//g' | grep -q '^{} ' || exec {}",
+            zellij_cmd, session_name, config.shell
         )
     };
 
+    // Recorded sessions capture the terminal stream with script(1)
+    let recording_id = if labels.get(RECORD_LABEL).map(String::as_str) == Some("true") {
+        Some(recording::new_recording_id())
+    } else {
+        None
+    };
+
     // Run interactive exec
     let shell_env = format!("/home/agent/.local/bin/{}-session", config.name);
     let mut cmd = Command::new(runtime.as_str());
     cmd.args(["exec", "-it"]);
     cmd.args(["-e", &format!("SHELL={}", shell_env)]);
     cmd.args(["-e", &format!("AGENT_LAUNCH_CMD={}", config.launch_cmd)]);
-    cmd.args([container_name, "bash", "-c", &zellij_cmd]);
+
+    match &recording_id {
+        Some(id) => {
+            cmd.args(["-e", &format!("KLOTHO_ATTACH_CMD={}", zellij_cmd)]);
+            cmd.args([container_name, "bash", "-c", &recording::wrap_command(id)]);
+        }
+        None => {
+            cmd.args([container_name, "bash", "-c", &zellij_cmd]);
+        }
+    }
 
     // This is interactive - inherit stdio for TTY
     cmd.stdin(Stdio::inherit());
@@ -342,6 +645,22 @@ fn attach_zellij(
 
     let status = cmd.status().context("Failed to attach to container")?;
 
+    if let Some(id) = recording_id {
+        let title = format!("klotho session {}", session_name);
+        let saved = recording::recordings_dir(session_name)
+            .and_then(|dir| recording::finalize(&dir, &id, &title));
+        if let Err(e) = saved {
+            eprintln!("warning: failed to save recording: {}", e);
+        }
+    }
+
+    // Detaching leaves the session running; only a finished session ends an ephemeral one
+    if ephemeral && !zellij_session_active(runtime, container_name, session_name)? {
+        stop_container(runtime, container_name)?;
+        // The user just left the session at this terminal, so the worktree is offered for removal
+        return rm::cleanup_ephemeral(runtime, &labels, &config.name, session_name, false);
+    }
+
     if !status.success() {
         bail!("Failed to attach to session");
     }
@@ -1,18 +1,42 @@
-use anyhow::{bail, Result};
-use crate::container::{detect_runtime, find_container, stop_container};
+use anyhow::Result;
+use crate::commands::rm::cleanup_ephemeral;
+use crate::container::{
+    detect_runtime, get_container_labels, stop_container, ContainerStatus, EPHEMERAL_LABEL,
+};
+use crate::selection::{self, Selector};
 
-pub fn run(name: String, runtime_override: Option<&str>) -> Result<()> {
+pub fn run(selector: Selector, force: bool, runtime_override: Option<&str>) -> Result<()> {
     let runtime = detect_runtime(runtime_override)?;
 
-    // Find container by session name
-    let container_name = match find_container(runtime, &name)? {
-        Some(name) => name,
-        None => bail!("Session '{}' not found", name),
-    };
+    // Find containers by session name, pattern or filter
+    let mut sessions = selection::select(runtime, &selector)?;
 
-    // Stop container (idempotent)
-    stop_container(runtime, &container_name)?;
+    // A single named session is stopped as before; anything more is listed first
+    if !selector.is_single() || sessions.len() > 1 {
+        sessions.retain(|s| s.status == ContainerStatus::Running);
+        if sessions.is_empty() {
+            println!("No running sessions matched.");
+            return Ok(());
+        }
+        if !force && !selection::confirm("stop", &selection::describe(&sessions))? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    for session in &sessions {
+        // Ephemeral containers are gone once stopped, so read their labels first
+        let labels = get_container_labels(runtime, &session.container)?;
+
+        // Stop container (idempotent)
+        stop_container(runtime, &session.container)?;
+
+        if labels.get(EPHEMERAL_LABEL).map(String::as_str) == Some("true") {
+            cleanup_ephemeral(runtime, &labels, &session.agent, &session.name, force)?;
+        } else {
+            println!("Stopped: {}", session.name);
+        }
+    }
 
-    println!("Stopped: {}", name);
     Ok(())
 }
@@ -0,0 +1,244 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::commands::build;
+use crate::commands::start::{self, create_container};
+use crate::container::{
+    container_status, detect_runtime, find_container, get_container_labels,
+    get_container_mount_specs, get_container_workdir, remove_container, rename_container,
+    stop_container, ContainerStatus, EPHEMERAL_LABEL, LAYOUT_LABEL, MOUNTS_LABEL,
+};
+use crate::derived::{self, ProjectContainerfile, CONTAINERFILE_LABEL, PROJECT_LABEL};
+use crate::devcontainer::{self, DEVCONTAINER_LABEL};
+use crate::image::{
+    image_id, image_labels, image_ref, resolve_tag, session_images, AGENT_LABEL, IMAGE_ID_LABEL,
+    IMAGE_TAG_LABEL,
+};
+use crate::selection::{self, Session};
+
+/// Labels create_container sets itself, that only describe the image, or that point at
+/// files inside the old container
+const MANAGED_LABELS: [&str; 6] = [
+    IMAGE_ID_LABEL,
+    IMAGE_TAG_LABEL,
+    MOUNTS_LABEL,
+    EPHEMERAL_LABEL,
+    AGENT_LABEL,
+    LAYOUT_LABEL,
+];
+
+/// Recreate a session's container on a newer image with the same mounts, name and labels
+pub fn run(
+    name: String,
+    image_tag: Option<String>,
+    force: bool,
+    runtime_override: Option<&str>,
+) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    // Find container by session name
+    let container_name = match find_container(runtime, &name)? {
+        Some(name) => name,
+        None => bail!("Session '{}' not found", name),
+    };
+    let labels = get_container_labels(runtime, &container_name)?;
+    let agent = selection::session_agent(&labels, &container_name, &name)?;
+    let status = container_status(runtime, &container_name)?;
+
+    let devcontainer = match labels.get(DEVCONTAINER_LABEL) {
+        Some(path) => {
+            if image_tag.is_some() {
+                bail!(
+                    "--image-tag can't be used with a session created from {}",
+                    path
+                );
+            }
+            let project = devcontainer::project_dir(Path::new(path));
+            let dev = devcontainer::find(&project)?
+                .with_context(|| format!("{} no longer exists", path))?;
+            Some(dev)
+        }
+        None => None,
+    };
+
+    // Target image: the agent rebuilt on the devcontainer image, a pinned build, else latest
+    let target = match &devcontainer {
+        Some(dev) => build::build_on_base(runtime, &agent, &dev.base_image(runtime)?)?,
+        None => match &image_tag {
+            Some(tag) => resolve_tag(runtime, &agent, tag)?,
+            None => image_ref(&agent, "latest"),
+        },
+    };
+    image_id(runtime, &target).with_context(|| {
+        format!(
+            "no image to upgrade to\nbuild one with: klotho build {}",
+            agent
+        )
+    })?;
+
+    let session = Session::with_agent(&container_name, Some(&agent), status.clone());
+    let current = session_images(runtime, std::slice::from_ref(&session))?
+        .remove(&container_name)
+        .context("failed to read the session's image")?;
+    let old_image_labels = image_labels(runtime, &current.id).unwrap_or_default();
+
+    // Sessions on a project image get it rebuilt on top of the new agent image
+    let target = match old_image_labels.get(PROJECT_LABEL) {
+        Some(project) if Path::new(project).is_dir() => {
+            start::project_image(runtime, &agent, &target, Path::new(project))?
+        }
+        Some(project) => {
+            eprintln!(
+                "warning: {} no longer exists, upgrading onto the agent image",
+                project
+            );
+            target
+        }
+        // Project images from before the project label
+        None => match old_image_labels.get(CONTAINERFILE_LABEL) {
+            Some(path) if Path::new(path).is_file() => {
+                let file = ProjectContainerfile::read(Path::new(path))?;
+                derived::ensure(runtime, &agent, &target, &file)?
+            }
+            Some(path) => {
+                eprintln!(
+                    "warning: {} no longer exists, upgrading onto the agent image",
+                    path
+                );
+                target
+            }
+            None => target,
+        },
+    };
+    let target_id = image_id(runtime, &target)?;
+    if current.id == target_id && current.pinned == image_tag {
+        println!("Session '{}' is already on {}", name, target);
+        return Ok(());
+    }
+
+    let ephemeral = labels.get(EPHEMERAL_LABEL).map(String::as_str) == Some("true");
+    let mount_specs = get_container_mount_specs(runtime, &container_name, &labels)?;
+    let workdir = get_container_workdir(runtime, &container_name)?;
+    let mut label_args = carried_labels(&labels, &old_image_labels, image_tag.as_deref());
+    label_args.push(format!("--label={}={}", AGENT_LABEL, agent));
+
+    // Confirm unless --force
+    if !force {
+        println!("Recreating '{}' on {} keeps these mounts:", name, target);
+        for spec in &mount_specs {
+            println!("  {}", spec);
+        }
+        println!("Anything else in the container is lost, including running processes.");
+        if !selection::ask("Continue?")? {
+            println!("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    // Move the old container aside so it can be restored if creation fails
+    let old_name = format!("{}-upgrading", container_name);
+    rename_container(runtime, &container_name, &old_name)?;
+
+    // containerEnv and forwardPorts as devcontainer.json has them now
+    let mut run_args = match &devcontainer {
+        Some(dev) => dev.env_and_port_args(),
+        None => Vec::new(),
+    };
+    run_args.extend(
+        mount_specs
+            .iter()
+            .flat_map(|spec| ["-v".to_string(), spec.clone()]),
+    );
+    if let Err(e) = create_container(
+        runtime,
+        &container_name,
+        &target,
+        &workdir,
+        &label_args,
+        &run_args,
+        ephemeral,
+    ) {
+        rename_container(runtime, &old_name, &container_name)?;
+        return Err(e);
+    }
+
+    // Ephemeral containers are removed by the runtime once stopped
+    stop_container(runtime, &old_name)?;
+    if !ephemeral {
+        remove_container(runtime, &old_name)?;
+    }
+
+    // Leave the session as it was found
+    if status == ContainerStatus::Stopped {
+        stop_container(runtime, &container_name)?;
+    }
+
+    println!(
+        "{} Upgraded session '{}' → {}",
+        "✓".green(),
+        name.bold(),
+        target.cyan()
+    );
+    if status == ContainerStatus::Running {
+        println!("Attach with: klotho attach {}", name);
+    }
+
+    Ok(())
+}
+
+/// Build `--label` arguments carrying a session's own labels over to its new container
+///
+/// Labels inherited from the old image and labels create_container sets are dropped.
+fn carried_labels(
+    labels: &HashMap<String, String>,
+    image_labels: &HashMap<String, String>,
+    image_tag: Option<&str>,
+) -> Vec<String> {
+    let mut args: Vec<String> = labels
+        .iter()
+        .filter(|(key, _)| key.starts_with("klotho.") && !MANAGED_LABELS.contains(&key.as_str()))
+        .filter(|(key, value)| image_labels.get(*key) != Some(*value))
+        .map(|(key, value)| format!("--label={}={}", key, value))
+        .collect();
+
+    if let Some(tag) = image_tag {
+        args.push(format!("--label={}={}", IMAGE_TAG_LABEL, tag));
+    }
+
+    args.sort();
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_carried_labels() {
+        let labels = HashMap::from([
+            ("klotho".to_string(), "true".to_string()),
+            ("klotho.record".to_string(), "true".to_string()),
+            ("klotho.worktree.branch".to_string(), "fix".to_string()),
+            (IMAGE_ID_LABEL.to_string(), "abc".to_string()),
+            (IMAGE_TAG_LABEL.to_string(), "20240101-000000".to_string()),
+            (AGENT_LABEL.to_string(), "claude".to_string()),
+            ("klotho.build".to_string(), "x".to_string()),
+            ("io.buildah.version".to_string(), "1.0".to_string()),
+        ]);
+        let image_labels = HashMap::from([("klotho.build".to_string(), "x".to_string())]);
+
+        assert_eq!(
+            carried_labels(&labels, &image_labels, None),
+            vec![
+                "--label=klotho.record=true".to_string(),
+                "--label=klotho.worktree.branch=fix".to_string(),
+            ]
+        );
+        assert!(
+            carried_labels(&labels, &image_labels, Some("20240202-000000"))
+                .contains(&"--label=klotho.image.tag=20240202-000000".to_string())
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use anyhow::{bail, Result};
+
+use crate::container::detect_runtime;
+use crate::selection::{self, list_sessions, session_names};
+use crate::volume;
+
+/// List home volumes with the sessions using them
+pub fn run_ls(runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let volumes = volume::list(runtime)?;
+    if volumes.is_empty() {
+        println!("No home volumes found.");
+        println!("Declare paths to keep with AGENT_HOME_VOLUMES in the agent config.");
+        return Ok(());
+    }
+    let sessions = list_sessions(runtime)?;
+
+    // Print table header
+    println!("{:<50} {:<16} {:<20} USED BY", "VOLUME", "SCOPE", "PATH");
+    println!("{}", "-".repeat(100));
+
+    for home_volume in &volumes {
+        let users = session_names(&sessions, &volume::users(runtime, &home_volume.name)?);
+        let users = if users.is_empty() {
+            "-".to_string()
+        } else {
+            users.join(", ")
+        };
+
+        println!(
+            "{:<50} {:<16} {:<20} {}",
+            home_volume.name,
+            home_volume.scope(),
+            home_volume.path,
+            users
+        );
+    }
+
+    Ok(())
+}
+
+/// Remove home volumes no container uses
+pub fn run_rm(names: Vec<String>, force: bool, runtime_override: Option<&str>) -> Result<()> {
+    let runtime = detect_runtime(runtime_override)?;
+
+    let volumes = volume::list(runtime)?;
+    for name in &names {
+        if !volumes.iter().any(|v| &v.name == name) {
+            bail!(
+                "Volume '{}' not found\nList volumes with: klotho volumes ls",
+                name
+            );
+        }
+
+        let users = volume::users(runtime, name)?;
+        if !users.is_empty() {
+            bail!(
+                "Volume '{}' is used by {}\nRemove the session first: klotho rm",
+                name,
+                users.join(", ")
+            );
+        }
+    }
+
+    // Confirm unless --force
+    if !force && !selection::confirm_items("remove", "volume", &names)? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    for name in &names {
+        volume::remove(runtime, name)?;
+        println!("Removed: {}", name);
+    }
+
+    Ok(())
+}
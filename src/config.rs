@@ -1,6 +1,7 @@
 use crate::agent::AgentConfig;
 use crate::resources;
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
@@ -18,6 +19,32 @@ fn get_xdg_config_home() -> PathBuf {
     }
 }
 
+/// Get klotho state directory (recordings, logs and other generated data)
+///
+/// Checks XDG_STATE_HOME environment variable, falls back to ~/.local/state/klotho
+pub fn get_state_home() -> PathBuf {
+    if let Ok(xdg) = env::var("XDG_STATE_HOME") {
+        PathBuf::from(xdg).join("klotho")
+    } else if let Ok(home) = env::var("HOME") {
+        PathBuf::from(home).join(".local/state/klotho")
+    } else {
+        PathBuf::from(".local/state/klotho")
+    }
+}
+
+/// Get klotho data directory (user work managed by klotho, such as worktrees)
+///
+/// Checks XDG_DATA_HOME environment variable, falls back to ~/.local/share/klotho
+pub fn get_data_home() -> PathBuf {
+    if let Ok(xdg) = env::var("XDG_DATA_HOME") {
+        PathBuf::from(xdg).join("klotho")
+    } else if let Ok(home) = env::var("HOME") {
+        PathBuf::from(home).join(".local/share/klotho")
+    } else {
+        PathBuf::from(".local/share/klotho")
+    }
+}
+
 /// Get config home with XDG fallback and klotho/agent-session layering
 ///
 /// Priority:
@@ -48,21 +75,26 @@ pub fn get_config_home() -> (PathBuf, bool) {
 /// 2. User config in ~/.config/agent-session/agents/<agent>/config.conf (legacy)
 /// 3. Embedded default config (compiled into binary)
 ///
+/// Agents without an embedded config (e.g. imported with `klotho import`) need
+/// a complete user config.
+///
 /// Returns (config, used_legacy_path)
 pub fn load_agent_config(agent: &str) -> Result<(AgentConfig, bool)> {
-    // Load embedded default first (must exist)
-    let embedded_content = resources::get_agent_config(agent).map_err(|_| {
-        let available = resources::list_embedded_agents().join(", ");
-        anyhow::anyhow!("unknown agent: {}\navailable agents: {}", agent, available)
-    })?;
-
-    let mut config =
-        AgentConfig::from_keyvalue(&embedded_content).context("failed to parse embedded config")?;
-
-    // Check for user config override
     let (config_home, is_legacy) = get_config_home();
     let user_config_path = config_home.join("agents").join(agent).join("config.conf");
 
+    // Load embedded default first (must exist unless the user config stands alone)
+    let mut config = match resources::get_agent_config(agent) {
+        Ok(embedded_content) => AgentConfig::from_keyvalue(&embedded_content)
+            .context("failed to parse embedded config")?,
+        Err(_) if user_config_path.exists() => HashMap::new(),
+        Err(_) => {
+            let available = resources::list_embedded_agents().join(", ");
+            anyhow::bail!("unknown agent: {}\navailable agents: {}", agent, available)
+        }
+    };
+
+    // Check for user config override
     if user_config_path.exists() {
         let user_content =
             fs::read_to_string(&user_config_path).context("failed to read user config file")?;
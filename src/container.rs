@@ -1,6 +1,9 @@
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::process::Command;
 
+use crate::time::parse_timestamp;
+
 /// Container runtime (podman or docker)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Runtime {
@@ -21,6 +24,15 @@ impl Runtime {
     }
 }
 
+/// Container label marking a session as ephemeral (removed when it stops)
+pub const EPHEMERAL_LABEL: &str = "klotho.ephemeral";
+
+/// Container label holding the `-v` mount specs a session was created with (JSON array)
+pub const MOUNTS_LABEL: &str = "klotho.mounts";
+
+/// Container label with the path of the Zellij layout a session's Zellij session starts from
+pub const LAYOUT_LABEL: &str = "klotho.layout";
+
 /// Container status
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ContainerStatus {
@@ -84,11 +96,16 @@ pub fn detect_runtime(runtime_override: Option<&str>) -> Result<Runtime> {
 
 /// Check if runtime is available
 fn is_runtime_available(runtime: Runtime) -> bool {
-    Command::new(runtime.as_str())
-        .arg("--version")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false)
+    runtime_version(runtime).is_some()
+}
+
+/// Version of a runtime as reported by `--version`, `None` when it isn't installed
+pub fn runtime_version(runtime: Runtime) -> Option<String> {
+    let output = runtime.command().arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
 }
 
 /// Ensure runtime is available, error if not
@@ -156,9 +173,10 @@ pub fn image_exists(runtime: Runtime, agent: &str) -> Result<bool> {
 
 /// Check if specific image exists
 fn check_image_exists(runtime: Runtime, image_name: &str) -> Result<bool> {
+    // `image inspect` rather than podman's `image exists`, which docker lacks
     let output = runtime
         .command()
-        .args(["image", "exists", image_name])
+        .args(["image", "inspect", "--format", "{{.Id}}", image_name])
         .output()
         .context("failed to check image existence")?;
 
@@ -255,6 +273,237 @@ pub fn list_containers(runtime: Runtime) -> Result<Vec<(String, ContainerStatus)
     Ok(containers)
 }
 
+/// Get labels set on a container
+pub fn get_container_labels(
+    runtime: Runtime,
+    container_name: &str,
+) -> Result<HashMap<String, String>> {
+    let output = runtime
+        .command()
+        .args([
+            "inspect",
+            "--format",
+            "{{json .Config.Labels}}",
+            container_name,
+        ])
+        .output()
+        .context("failed to inspect container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to inspect container: {}", stderr);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let labels: Option<HashMap<String, String>> =
+        serde_json::from_str(stdout.trim()).context("failed to parse container labels")?;
+
+    Ok(labels.unwrap_or_default())
+}
+
+/// Get bind mounts of a container as (host source, container destination) pairs
+pub fn get_container_mounts(
+    runtime: Runtime,
+    container_name: &str,
+) -> Result<Vec<(String, String)>> {
+    let output = runtime
+        .command()
+        .args([
+            "inspect",
+            "--format",
+            "{{range .Mounts}}{{.Source}}|{{.Destination}}\n{{end}}",
+            container_name,
+        ])
+        .output()
+        .context("failed to inspect container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to inspect container: {}", stderr);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| line.trim().split_once('|'))
+        .map(|(src, dst)| (src.to_string(), dst.to_string()))
+        .collect())
+}
+
+/// Get the `-v` specs that recreate a container's mounts
+///
+/// Uses the specs recorded in the mounts label at creation. Containers created
+/// before the label existed fall back to inspecting their mounts.
+pub fn get_container_mount_specs(
+    runtime: Runtime,
+    container_name: &str,
+    labels: &HashMap<String, String>,
+) -> Result<Vec<String>> {
+    if let Some(specs) = labels.get(MOUNTS_LABEL) {
+        return serde_json::from_str(specs).context("failed to parse recorded mounts");
+    }
+
+    let output = runtime
+        .command()
+        .args([
+            "inspect",
+            "--format",
+            "{{range .Mounts}}{{.Type}}|{{.Name}}|{{.Source}}|{{.Destination}}|{{.RW}}|{{.Mode}}\n{{end}}",
+            container_name,
+        ])
+        .output()
+        .context("failed to inspect container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to inspect container: {}", stderr);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().filter_map(parse_mount_spec).collect())
+}
+
+/// Turn an inspected mount ("type|name|source|destination|rw|mode") into a `-v` spec
+fn parse_mount_spec(line: &str) -> Option<String> {
+    let fields: Vec<&str> = line.trim().split('|').collect();
+    let [kind, name, source, destination, rw, mode] = fields[..] else {
+        return None;
+    };
+
+    let source = match kind {
+        "volume" => name,
+        "bind" => source,
+        _ => return None,
+    };
+
+    let mut options = Vec::new();
+    if rw == "false" {
+        options.push("ro");
+    }
+    if mode.contains('Z') {
+        options.push("Z");
+    } else if mode.contains('z') {
+        options.push("z");
+    } else if kind == "bind" && mode.is_empty() && is_workspace_mount(destination) {
+        // Not every runtime reports the relabel option; projects are always relabelled
+        options.push("Z");
+    }
+
+    if options.is_empty() {
+        Some(format!("{}:{}", source, destination))
+    } else {
+        Some(format!("{}:{}:{}", source, destination, options.join(",")))
+    }
+}
+
+/// Get a container's working directory
+pub fn get_container_workdir(runtime: Runtime, container_name: &str) -> Result<String> {
+    let output = runtime
+        .command()
+        .args([
+            "inspect",
+            "--format",
+            "{{.Config.WorkingDir}}",
+            container_name,
+        ])
+        .output()
+        .context("failed to inspect container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to inspect container: {}", stderr);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Get host paths of the project directories mounted into a session
+///
+/// Projects are mounted at /workspace (single) or /workspace1..N (multiple).
+pub fn get_project_dirs(runtime: Runtime, container_name: &str) -> Result<Vec<String>> {
+    let mut projects: Vec<(String, String)> = get_container_mounts(runtime, container_name)?
+        .into_iter()
+        .filter(|(_, dst)| is_workspace_mount(dst))
+        .collect();
+
+    // Keep mount order stable: /workspace, /workspace1, /workspace2, ...
+    projects.sort_by_key(|(_, dst)| {
+        dst.trim_start_matches("/workspace")
+            .parse::<usize>()
+            .unwrap_or(0)
+    });
+
+    Ok(projects.into_iter().map(|(src, _)| src).collect())
+}
+
+/// Check if a container path is a project mount point
+fn is_workspace_mount(destination: &str) -> bool {
+    match destination.strip_prefix("/workspace") {
+        Some("") => true,
+        Some(n) => n.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// List names of containers carrying a label set to "true"
+pub fn list_containers_with_label(
+    runtime: Runtime,
+    label: &str,
+) -> Result<std::collections::HashSet<String>> {
+    let output = runtime
+        .command()
+        .args([
+            "ps",
+            "-a",
+            "--filter",
+            &format!("label={}=true", label),
+            "--format",
+            "{{.Names}}",
+        ])
+        .output()
+        .context("failed to list containers")?;
+
+    if !output.status.success() {
+        anyhow::bail!("failed to list containers");
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// Map container names to the value of a label, for containers that carry it
+pub fn list_container_label(
+    runtime: Runtime,
+    label: &str,
+) -> Result<std::collections::HashMap<String, String>> {
+    let output = runtime
+        .command()
+        .args([
+            "ps",
+            "-a",
+            "--filter",
+            &format!("label={}", label),
+            "--format",
+            &format!("{{{{.Names}}}}|{{{{.Label \"{}\"}}}}", label),
+        ])
+        .output()
+        .context("failed to list containers")?;
+
+    if !output.status.success() {
+        anyhow::bail!("failed to list containers");
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().split_once('|'))
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect())
+}
+
 /// Find container by session name
 pub fn find_container(runtime: Runtime, session_name: &str) -> Result<Option<String>> {
     let containers = list_containers(runtime)?;
@@ -314,6 +563,22 @@ pub fn start_container(runtime: Runtime, container_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Rename container
+pub fn rename_container(runtime: Runtime, container_name: &str, new_name: &str) -> Result<()> {
+    let output = runtime
+        .command()
+        .args(["rename", container_name, new_name])
+        .output()
+        .context("failed to rename container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to rename container: {}", stderr);
+    }
+
+    Ok(())
+}
+
 /// Remove container
 pub fn remove_container(runtime: Runtime, container_name: &str) -> Result<()> {
     let output = runtime
@@ -330,6 +595,143 @@ pub fn remove_container(runtime: Runtime, container_name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Get when a container last stopped, or was created if it never ran (Unix time)
+pub fn container_stopped_at(runtime: Runtime, container_name: &str) -> Result<Option<u64>> {
+    let output = runtime
+        .command()
+        .args([
+            "inspect",
+            "--format",
+            "{{.State.FinishedAt}}|{{.Created}}",
+            container_name,
+        ])
+        .output()
+        .context("failed to inspect container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to inspect container: {}", stderr);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (finished, created) = stdout.trim().split_once('|').unwrap_or((stdout.trim(), ""));
+    Ok(parse_timestamp(finished).or_else(|| parse_timestamp(created)))
+}
+
+/// Check if a Zellij session exists inside a container
+///
+/// Exited sessions that Zellij can resurrect count as existing.
+pub fn zellij_session_exists(
+    runtime: Runtime,
+    container_name: &str,
+    session_name: &str,
+) -> Result<bool> {
+    Ok(list_zellij_sessions(runtime, container_name)?
+        .iter()
+        .any(|(name, _)| name == session_name))
+}
+
+/// Check if a Zellij session is running (not exited) inside a container
+pub fn zellij_session_active(
+    runtime: Runtime,
+    container_name: &str,
+    session_name: &str,
+) -> Result<bool> {
+    Ok(list_zellij_sessions(runtime, container_name)?
+        .iter()
+        .any(|(name, exited)| name == session_name && !exited))
+}
+
+/// List Zellij sessions inside a container as (name, exited) pairs
+fn list_zellij_sessions(runtime: Runtime, container_name: &str) -> Result<Vec<(String, bool)>> {
+    let check = runtime
+        .command()
+        .args(["exec", container_name, "zellij", "list-sessions"])
+        .output()
+        .context("failed to list zellij sessions")?;
+
+    let stdout = String::from_utf8_lossy(&check.stdout);
+    // Strip ANSI codes for comparison (regex pattern: \x1b\[[0-9;]*m)
+    Ok(parse_zellij_sessions(&strip_ansi_codes(&stdout)))
+}
+
+/// Parse `zellij list-sessions` output, e.g. "name [Created 1h ago] (EXITED - ...)"
+fn parse_zellij_sessions(output: &str) -> Vec<(String, bool)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let name = line.split_whitespace().next()?;
+            Some((name.to_string(), line.contains("EXITED")))
+        })
+        .collect()
+}
+
+/// Count clients attached to a Zellij session inside a container
+///
+/// Returns None if the session isn't running or the client list is unavailable
+pub fn zellij_client_count(
+    runtime: Runtime,
+    container_name: &str,
+    session_name: &str,
+) -> Option<usize> {
+    let output = runtime
+        .command()
+        .args([
+            "exec",
+            container_name,
+            "zellij",
+            "--session",
+            session_name,
+            "action",
+            "list-clients",
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(parse_client_count(&strip_ansi_codes(&stdout)))
+}
+
+/// Count client rows in `zellij action list-clients` output (first line is a header)
+fn parse_client_count(output: &str) -> usize {
+    output
+        .lines()
+        .skip(1)
+        .filter(|line| !line.trim().is_empty())
+        .count()
+}
+
+/// Strip ANSI escape codes from a string
+fn strip_ansi_codes(s: &str) -> String {
+    // ANSI escape sequences follow pattern: ESC [ <params> m
+    // where ESC is \x1b, params are digits/semicolons
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            // Check if this is start of ANSI sequence
+            if chars.peek() == Some(&'[') {
+                chars.next(); // consume '['
+                // Skip until we hit 'm' (or end of string)
+                for ch in chars.by_ref() {
+                    if ch == 'm' {
+                        break;
+                    }
+                }
+                continue;
+            }
+        }
+        result.push(ch);
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -340,6 +742,62 @@ mod tests {
         assert_eq!(Runtime::Docker.as_str(), "docker");
     }
 
+    #[test]
+    fn test_strip_ansi_codes() {
+        assert_eq!(
+            strip_ansi_codes("\x1b[32;1mdefault\x1b[m [Created 1h ago]"),
+            "default [Created 1h ago]"
+        );
+    }
+
+    #[test]
+    fn test_parse_mount_spec() {
+        assert_eq!(
+            parse_mount_spec("bind||/home/u/proj|/workspace|true|Z").as_deref(),
+            Some("/home/u/proj:/workspace:Z")
+        );
+        assert_eq!(
+            parse_mount_spec("bind||/home/u/proj|/workspace|true|").as_deref(),
+            Some("/home/u/proj:/workspace:Z")
+        );
+        assert_eq!(
+            parse_mount_spec("bind||/home/u/.claude|/home/agent/.claude|true|").as_deref(),
+            Some("/home/u/.claude:/home/agent/.claude")
+        );
+        assert_eq!(
+            parse_mount_spec("volume|klotho-home|/var/lib/x|/home/agent|false|z").as_deref(),
+            Some("klotho-home:/home/agent:ro,z")
+        );
+        assert_eq!(parse_mount_spec("tmpfs|||/tmp|true|"), None);
+    }
+
+    #[test]
+    fn test_parse_zellij_sessions() {
+        let output = "default [Created 2h ago] (current)\nold [Created 1d ago] (EXITED - attach to resurrect)\n";
+        assert_eq!(
+            parse_zellij_sessions(output),
+            vec![("default".to_string(), false), ("old".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn test_parse_client_count() {
+        let output = "CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n1         terminal_0     claude\n2         terminal_0     claude\n";
+        assert_eq!(parse_client_count(output), 2);
+        assert_eq!(
+            parse_client_count("CLIENT_ID ZELLIJ_PANE_ID RUNNING_COMMAND\n"),
+            0
+        );
+    }
+
+    #[test]
+    fn test_is_workspace_mount() {
+        assert!(is_workspace_mount("/workspace"));
+        assert!(is_workspace_mount("/workspace2"));
+        assert!(!is_workspace_mount("/workspace/sub"));
+        assert!(!is_workspace_mount("/home/agent/.claude"));
+    }
+
     #[test]
     fn test_detect_runtime_invalid() {
         let result = detect_runtime(Some("invalid"));
@@ -0,0 +1,407 @@
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::agent::AgentConfig;
+use crate::config::get_config_home;
+
+/// Image label with a hash of the customization an image was built with
+pub const INPUTS_LABEL: &str = "klotho.build.inputs";
+
+/// Line in the Containerfile's base stage replaced by the customization steps
+pub const CUSTOMIZE_MARKER: &str = "# @klotho:customize";
+
+/// Directory in the build context holding files to COPY
+const CONTEXT_DIR: &str = "custom";
+
+/// A file or directory copied into the image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyFile {
+    /// Absolute path on the host
+    pub source: PathBuf,
+    /// Absolute path in the image
+    pub dest: String,
+}
+
+/// Image customization declared by the global, agent and project configs
+///
+/// Keys (KEY=value, same format as agent configs):
+/// - `BUILD_BASE_IMAGE`: replaces debian:bookworm-slim, the most specific config wins
+/// - `BUILD_PACKAGES`: extra apt packages, space-separated
+/// - `BUILD_RUN`: a shell command run as the agent user
+/// - `BUILD_COPY`: `source:dest` pairs, space-separated
+///
+/// Packages, commands and files add up across configs. The global and agent
+/// configs go into the shared agent image; a project's is built as a derived
+/// image on top of it (see [`crate::derived::ensure_customized`]).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Customization {
+    pub base_image: Option<String>,
+    pub packages: Vec<String>,
+    pub steps: Vec<String>,
+    pub files: Vec<CopyFile>,
+}
+
+impl Customization {
+    /// Load the customization of an agent's shared image
+    ///
+    /// Layers, least specific first: ~/.config/klotho/config.conf, the agent's
+    /// config (`agent_config`, already parsed) and the user's override in
+    /// ~/.config/klotho/agents/<agent>/config.conf.
+    pub fn load(agent: &str, agent_config: &HashMap<String, String>) -> Result<Self> {
+        let mut custom = Customization::default();
+        let (config_home, _) = get_config_home();
+
+        let global = config_home.join("config.conf");
+        if let Some(map) = read_config(&global)? {
+            custom.apply(&map, &config_home)?;
+        }
+
+        custom.apply(agent_config, &config_home.join("agents").join(agent))?;
+
+        let user_agent_dir = config_home.join("agents").join(agent);
+        if let Some(map) = read_config(&user_agent_dir.join("config.conf"))? {
+            custom.apply(&map, &user_agent_dir)?;
+        }
+
+        Ok(custom)
+    }
+
+    /// Load a project's customization from .klotho/config.conf in `dir` or its
+    /// nearest ancestor with one, `None` if it customizes nothing
+    pub fn project(dir: &Path) -> Result<Option<Self>> {
+        let Some(project) = find_project_config(dir) else {
+            return Ok(None);
+        };
+
+        // Project files are relative to the project root, not .klotho/
+        let mut custom = Customization::default();
+        if let Some(map) = read_config(&project.join(".klotho").join("config.conf"))? {
+            custom.apply(&map, &project)?;
+        }
+        Ok((custom != Customization::default()).then_some(custom))
+    }
+
+    /// Merge one config layer, resolving relative COPY sources against `dir`
+    fn apply(&mut self, map: &HashMap<String, String>, dir: &Path) -> Result<()> {
+        if let Some(image) = map.get("BUILD_BASE_IMAGE").map(|s| s.trim()) {
+            if image.contains(char::is_whitespace) {
+                bail!("invalid BUILD_BASE_IMAGE '{}'", image);
+            }
+            if !image.is_empty() {
+                self.base_image = Some(image.to_string());
+            }
+        }
+
+        if let Some(packages) = map.get("BUILD_PACKAGES") {
+            for package in packages.split_whitespace() {
+                if !package
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.:=~".contains(c))
+                {
+                    bail!("invalid package name in BUILD_PACKAGES: '{}'", package);
+                }
+                if !self.packages.iter().any(|p| p == package) {
+                    self.packages.push(package.to_string());
+                }
+            }
+        }
+
+        if let Some(step) = map.get("BUILD_RUN").map(|s| s.trim()) {
+            if !step.is_empty() {
+                self.steps.push(step.to_string());
+            }
+        }
+
+        if let Some(files) = map.get("BUILD_COPY") {
+            for pair in files.split_whitespace() {
+                let (source, dest) = pair.split_once(':').with_context(|| {
+                    format!("invalid BUILD_COPY entry '{}': expected source:dest", pair)
+                })?;
+                let source = dir.join(source);
+                if !source.exists() {
+                    bail!("BUILD_COPY source not found: {}", source.display());
+                }
+                let dest = if dest.starts_with('/') {
+                    dest.to_string()
+                } else {
+                    format!("/home/agent/{}", dest)
+                };
+                self.files.push(CopyFile { source, dest });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert the customization into a Containerfile
+    ///
+    /// The base stage's FROM image is swapped and the marker line is replaced
+    /// with package, COPY and RUN instructions.
+    pub fn compose(&self, containerfile: &str) -> String {
+        let mut lines = Vec::new();
+        for line in containerfile.lines() {
+            if line.trim() == CUSTOMIZE_MARKER {
+                lines.extend(self.instructions());
+            } else if let Some(image) = self.base_image.as_deref().filter(|_| is_base_from(line)) {
+                lines.push(replace_from_image(line, image));
+            } else {
+                lines.push(line.to_string());
+            }
+        }
+
+        let mut composed = lines.join("\n");
+        composed.push('\n');
+        composed
+    }
+
+    /// A Containerfile adding the packages, files and commands on top of an image
+    ///
+    /// The base image setting is left out: it only applies to full builds.
+    pub fn derive(&self, base: &str) -> String {
+        let mut lines = vec![format!("FROM {}", base)];
+        lines.extend(self.instructions());
+        let mut containerfile = lines.join("\n");
+        containerfile.push('\n');
+        containerfile
+    }
+
+    /// Containerfile instructions for packages, files and commands (run as the agent user)
+    fn instructions(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if !self.packages.is_empty() {
+            lines.push("USER root".to_string());
+            lines.push(format!(
+                "RUN apt-get update && apt-get install -y --no-install-recommends {} \\\n    && rm -rf /var/lib/apt/lists/*",
+                self.packages.join(" ")
+            ));
+            lines.push("USER agent".to_string());
+        }
+
+        for (i, file) in self.files.iter().enumerate() {
+            lines.push(format!(
+                "COPY --chown=agent:agent {}/{} {}",
+                CONTEXT_DIR,
+                context_name(i, file),
+                file.dest
+            ));
+        }
+
+        for step in &self.steps {
+            lines.push(format!("RUN {}", step));
+        }
+
+        lines
+    }
+
+    /// Copy the BUILD_COPY sources into a build context
+    pub fn write_files(&self, context: &Path) -> Result<()> {
+        let dir = context.join(CONTEXT_DIR);
+        for (i, file) in self.files.iter().enumerate() {
+            let target = dir.join(context_name(i, file));
+            copy_recursive(&file.source, &target).with_context(|| {
+                format!(
+                    "failed to copy {} into the build context",
+                    file.source.display()
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Hash of everything the customization adds, to tell when an image is stale
+    pub fn inputs_hash(&self) -> Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.base_image.as_deref().unwrap_or(""));
+        hasher.update("\0");
+        for line in self.instructions() {
+            hasher.update(line);
+            hasher.update("\n");
+        }
+        for file in &self.files {
+            hash_path(&mut hasher, &file.source)?;
+        }
+        Ok(format!("{:x}", hasher.finalize())[..16].to_string())
+    }
+}
+
+/// Read a KEY=value config file if it exists
+fn read_config(path: &Path) -> Result<Option<HashMap<String, String>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let map = AgentConfig::from_keyvalue(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(Some(map))
+}
+
+/// Nearest directory, from `dir` up, containing .klotho/config.conf
+fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(".klotho").join("config.conf").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Whether a line is the base stage's FROM instruction
+fn is_base_from(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    words
+        .first()
+        .is_some_and(|w| w.eq_ignore_ascii_case("FROM"))
+        && words.len() >= 2
+        && words[words.len() - 2].eq_ignore_ascii_case("AS")
+        && words[words.len() - 1] == "base"
+}
+
+/// Swap the image of a FROM line, keeping flags such as --platform
+fn replace_from_image(line: &str, image: &str) -> String {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+    if let Some(word) = words.iter_mut().skip(1).find(|w| !w.starts_with("--")) {
+        *word = image;
+    }
+    words.join(" ")
+}
+
+/// Name of a copied file inside the context directory
+fn context_name(index: usize, file: &CopyFile) -> String {
+    let name = file
+        .source
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+    format!("{}-{}", index, name)
+}
+
+fn copy_recursive(source: &Path, target: &Path) -> Result<()> {
+    if source.is_dir() {
+        fs::create_dir_all(target)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+        }
+    } else {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source, target)?;
+    }
+    Ok(())
+}
+
+/// Feed a file's contents, or a directory's names and contents, into a hash
+pub(crate) fn hash_path(hasher: &mut Sha256, path: &Path) -> Result<()> {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<_>>()?;
+        entries.sort();
+        for entry in entries {
+            hasher.update(entry.file_name().unwrap_or_default().as_encoded_bytes());
+            hash_path(hasher, &entry)?;
+        }
+    } else {
+        hasher
+            .update(fs::read(path).with_context(|| format!("failed to read {}", path.display()))?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTAINERFILE: &str = "FROM --platform=linux/amd64 debian:bookworm-slim AS base\n\
+                                 USER agent\n\
+                                 # @klotho:customize\n\
+                                 FROM base AS claude\n";
+
+    #[test]
+    fn test_compose_empty() {
+        let composed = Customization::default().compose(CONTAINERFILE);
+        assert_eq!(
+            composed,
+            "FROM --platform=linux/amd64 debian:bookworm-slim AS base\n\
+             USER agent\n\
+             FROM base AS claude\n"
+        );
+    }
+
+    #[test]
+    fn test_compose() {
+        let custom = Customization {
+            base_image: Some("python:3.12-slim-bookworm".to_string()),
+            packages: vec!["build-essential".to_string(), "pkg-config".to_string()],
+            steps: vec!["curl -sSf https://sh.rustup.rs | sh -s -- -y".to_string()],
+            files: vec![CopyFile {
+                source: PathBuf::from("/host/pip.conf"),
+                dest: "/home/agent/.config/pip/pip.conf".to_string(),
+            }],
+        };
+        let composed = custom.compose(CONTAINERFILE);
+        let lines: Vec<&str> = composed.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "FROM --platform=linux/amd64 python:3.12-slim-bookworm AS base"
+        );
+        assert_eq!(lines[2], "USER root");
+        assert!(lines[3].contains("install -y --no-install-recommends build-essential pkg-config"));
+        assert!(composed.contains("USER agent\nCOPY --chown=agent:agent custom/0-pip.conf /home/agent/.config/pip/pip.conf\nRUN curl"));
+        assert!(composed.ends_with("FROM base AS claude\n"));
+    }
+
+    #[test]
+    fn test_derive() {
+        let custom = Customization {
+            base_image: Some("python:3.12-slim-bookworm".to_string()),
+            packages: vec!["libpq-dev".to_string()],
+            steps: vec!["uv tool install ruff".to_string()],
+            files: Vec::new(),
+        };
+        assert_eq!(
+            custom.derive("klotho-claude:latest"),
+            "FROM klotho-claude:latest\n\
+             USER root\n\
+             RUN apt-get update && apt-get install -y --no-install-recommends libpq-dev \\\n    \
+             && rm -rf /var/lib/apt/lists/*\n\
+             USER agent\n\
+             RUN uv tool install ruff\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_layers() {
+        let dir = std::env::temp_dir();
+        let mut custom = Customization::default();
+        let global = HashMap::from([
+            (
+                "BUILD_BASE_IMAGE".to_string(),
+                "debian:trixie-slim".to_string(),
+            ),
+            ("BUILD_PACKAGES".to_string(), "jq ripgrep".to_string()),
+        ]);
+        let project = HashMap::from([
+            (
+                "BUILD_PACKAGES".to_string(),
+                "ripgrep python3-venv".to_string(),
+            ),
+            ("BUILD_RUN".to_string(), "uv tool install ruff".to_string()),
+            ("BUILD_COPY".to_string(), ".:.config/extra".to_string()),
+        ]);
+        custom.apply(&global, &dir).unwrap();
+        custom.apply(&project, &dir).unwrap();
+
+        assert_eq!(custom.base_image.as_deref(), Some("debian:trixie-slim"));
+        assert_eq!(custom.packages, vec!["jq", "ripgrep", "python3-venv"]);
+        assert_eq!(custom.steps, vec!["uv tool install ruff"]);
+        assert_eq!(custom.files[0].dest, "/home/agent/.config/extra");
+
+        let bad = HashMap::from([("BUILD_PACKAGES".to_string(), "jq;rm".to_string())]);
+        assert!(custom.apply(&bad, &dir).is_err());
+    }
+}
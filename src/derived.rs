@@ -0,0 +1,296 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::container::Runtime;
+use crate::customization::{hash_path, Customization};
+use crate::image::{
+    image_id, image_labels, image_ref, AGENT_LABEL, IMAGE_LABEL, PROJECT_TAG_PREFIX,
+};
+
+/// Image label with the ID of the agent image a derived image was built on
+pub const BASE_ID_LABEL: &str = "klotho.image.base";
+
+/// Image label with the project Containerfile a derived image was built from
+pub const CONTAINERFILE_LABEL: &str = "klotho.image.containerfile";
+
+/// Image label with the project directory a derived image was built for
+pub const PROJECT_LABEL: &str = "klotho.image.project";
+
+/// Placeholder for the agent image in a project Containerfile
+pub const PLACEHOLDER: &str = "klotho-<agent>";
+
+/// Project files checked for a derived image, in order
+///
+/// `.devcontainer/` files are only used when they derive from the placeholder.
+const CANDIDATES: [&str; 4] = [
+    ".klotho/Containerfile",
+    ".klotho/Dockerfile",
+    ".devcontainer/Containerfile",
+    ".devcontainer/Dockerfile",
+];
+
+/// A project Containerfile building on the agent image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectContainerfile {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+impl ProjectContainerfile {
+    /// Read a project Containerfile at a known path
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(ProjectContainerfile {
+            path: path.to_path_buf(),
+            content,
+        })
+    }
+
+    /// Directory used as build context: the one holding the file
+    fn context_dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    /// The project the file belongs to: the parent of its .klotho/ or .devcontainer/
+    fn project_dir(&self) -> Option<&Path> {
+        self.context_dir().parent()
+    }
+
+    /// The Containerfile with the placeholder pointing at a concrete agent image
+    pub fn compose(&self, base: &str) -> String {
+        self.content.replace(PLACEHOLDER, base)
+    }
+
+    /// Tag of the derived image: hash of the file, the rest of its build context
+    /// directory (files it may COPY) and the agent image it builds on
+    pub fn tag(&self, base_id: &str) -> Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.content);
+        hasher.update("\0");
+        if self.context_dir().is_dir() {
+            hash_path(&mut hasher, self.context_dir())?;
+        }
+        hasher.update("\0");
+        hasher.update(base_id);
+        let hash = format!("{:x}", hasher.finalize());
+        Ok(format!("{}{}", PROJECT_TAG_PREFIX, &hash[..12]))
+    }
+}
+
+/// Find the project Containerfile for a project directory, if any
+pub fn find(project: &Path) -> Result<Option<ProjectContainerfile>> {
+    for candidate in CANDIDATES {
+        let path = project.join(candidate);
+        if !path.is_file() {
+            continue;
+        }
+
+        let file = ProjectContainerfile::read(&path)?;
+        if candidate.starts_with(".klotho/") || file.content.contains(PLACEHOLDER) {
+            return Ok(Some(file));
+        }
+    }
+    Ok(None)
+}
+
+/// Get the derived image for a project Containerfile on top of `base`, building it if needed
+///
+/// Returns the image reference, e.g. "klotho-claude:project-3f2a9c81d0e4".
+pub fn ensure(
+    runtime: Runtime,
+    agent: &str,
+    base: &str,
+    file: &ProjectContainerfile,
+) -> Result<String> {
+    let base_id = image_id(runtime, base)?;
+    let reference = image_ref(agent, &file.tag(&base_id)?);
+    if image_id(runtime, &reference).is_ok() {
+        return Ok(reference);
+    }
+
+    eprintln!(
+        "{} Building project image from {}",
+        "→".cyan(),
+        file.path.display()
+    );
+
+    // The composed file lives outside the project so the tree stays untouched
+    let composed_path = std::env::temp_dir().join(format!(
+        "klotho-derived-{}.Containerfile",
+        reference.replace([':', '/'], "-")
+    ));
+    std::fs::write(&composed_path, file.compose(base))
+        .context("failed to write derived Containerfile")?;
+
+    let mut labels = vec![format!("{}={}", CONTAINERFILE_LABEL, file.path.display())];
+    if let Some(project) = file.project_dir() {
+        labels.push(format!("{}={}", PROJECT_LABEL, project.display()));
+    }
+    let result = build(
+        runtime,
+        agent,
+        base,
+        &reference,
+        &labels,
+        &composed_path,
+        file.context_dir(),
+    );
+    let _ = std::fs::remove_file(&composed_path);
+    result
+        .with_context(|| format!("failed to build project image from {}", file.path.display()))?;
+
+    eprintln!("{} Built {}", "✓".green(), reference.cyan());
+    Ok(reference)
+}
+
+/// Get the image with a project's .klotho/config.conf customization on top of `base`,
+/// building it if needed
+///
+/// Returns the image reference, tagged like the Containerfile images.
+pub fn ensure_customized(
+    runtime: Runtime,
+    agent: &str,
+    base: &str,
+    project: &Path,
+    custom: &Customization,
+) -> Result<String> {
+    let base_id = image_id(runtime, base)?;
+    let mut hasher = Sha256::new();
+    hasher.update(custom.inputs_hash()?);
+    hasher.update("\0");
+    hasher.update(&base_id);
+    let hash = format!("{:x}", hasher.finalize());
+    let reference = image_ref(agent, &format!("{}{}", PROJECT_TAG_PREFIX, &hash[..12]));
+    if image_id(runtime, &reference).is_ok() {
+        return Ok(reference);
+    }
+
+    eprintln!(
+        "{} Building project image for {}",
+        "→".cyan(),
+        project.display()
+    );
+
+    // BUILD_COPY sources are copied into a scratch context next to the Containerfile
+    let context = std::env::temp_dir().join(format!(
+        "klotho-derived-{}",
+        reference.replace([':', '/'], "-")
+    ));
+    let result = (|| -> Result<()> {
+        std::fs::create_dir_all(&context).context("failed to create build context")?;
+        let containerfile = context.join("Containerfile");
+        std::fs::write(&containerfile, custom.derive(base))
+            .context("failed to write derived Containerfile")?;
+        custom.write_files(&context)?;
+
+        let labels = [format!("{}={}", PROJECT_LABEL, project.display())];
+        build(
+            runtime,
+            agent,
+            base,
+            &reference,
+            &labels,
+            &containerfile,
+            &context,
+        )
+    })();
+    let _ = std::fs::remove_dir_all(&context);
+    result.with_context(|| format!("failed to build project image for {}", project.display()))?;
+
+    eprintln!("{} Built {}", "✓".green(), reference.cyan());
+    Ok(reference)
+}
+
+/// Build a derived image of `base` and tag it `reference`
+///
+/// `labels` (key=value) are added to the klotho, agent and base image labels.
+fn build(
+    runtime: Runtime,
+    agent: &str,
+    base: &str,
+    reference: &str,
+    labels: &[String],
+    containerfile: &Path,
+    context: &Path,
+) -> Result<()> {
+    // Images derived from a derived image still point at the agent image underneath
+    let base_id = match image_labels(runtime, base)?.remove(BASE_ID_LABEL) {
+        Some(id) => id,
+        None => image_id(runtime, base)?,
+    };
+
+    let output = runtime
+        .command()
+        .arg("build")
+        .arg("-t")
+        .arg(reference)
+        .arg("--label")
+        .arg(format!("{}=true", IMAGE_LABEL))
+        .arg("--label")
+        .arg(format!("{}={}", AGENT_LABEL, agent))
+        .arg("--label")
+        .arg(format!("{}={}", BASE_ID_LABEL, base_id))
+        .args(labels.iter().flat_map(|label| ["--label", label.as_str()]))
+        .arg("-f")
+        .arg(containerfile)
+        .arg(context)
+        .output()
+        .context("failed to start build command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+        bail!("{}", tail.into_iter().rev().collect::<Vec<_>>().join("\n"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_and_tag() {
+        let file = ProjectContainerfile {
+            path: PathBuf::from("/project/.klotho/Containerfile"),
+            content: "FROM klotho-<agent>\nUSER root\nRUN apt-get install -y libpq-dev\n"
+                .to_string(),
+        };
+        assert_eq!(
+            file.compose("klotho-claude:latest"),
+            "FROM klotho-claude:latest\nUSER root\nRUN apt-get install -y libpq-dev\n"
+        );
+
+        let tag = file.tag("abc").unwrap();
+        assert!(tag.starts_with(PROJECT_TAG_PREFIX));
+        assert_eq!(tag.len(), PROJECT_TAG_PREFIX.len() + 12);
+        assert_eq!(tag, file.tag("abc").unwrap());
+        assert_ne!(tag, file.tag("def").unwrap());
+        assert_eq!(file.context_dir(), Path::new("/project/.klotho"));
+    }
+
+    #[test]
+    fn test_tag_covers_context() {
+        let dir = std::env::temp_dir()
+            .join(format!("klotho-derived-test-{}", std::process::id()))
+            .join(".klotho");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("Containerfile"),
+            "FROM klotho-<agent>\nCOPY setup.sh /tmp/\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("setup.sh"), "echo one\n").unwrap();
+
+        let file = ProjectContainerfile::read(&dir.join("Containerfile")).unwrap();
+        let before = file.tag("abc").unwrap();
+        std::fs::write(dir.join("setup.sh"), "echo two\n").unwrap();
+        let after = file.tag("abc").unwrap();
+
+        let _ = std::fs::remove_dir_all(dir.parent().unwrap());
+        assert_ne!(before, after);
+    }
+}
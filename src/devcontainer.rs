@@ -0,0 +1,637 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::container::Runtime;
+use crate::image::image_id;
+
+/// Container label with the devcontainer.json a session was created from
+pub const DEVCONTAINER_LABEL: &str = "klotho.devcontainer";
+
+/// Where the project is mounted inside session containers
+const CONTAINER_WORKSPACE: &str = "/workspace";
+
+/// Repository for images built from a devcontainer Dockerfile
+///
+/// Not prefixed `klotho-`: these are base images, not agent images.
+const BASE_REPOSITORY: &str = "klotho_devcontainer";
+
+/// The subset of devcontainer.json klotho understands
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Spec {
+    image: Option<String>,
+    build: Option<BuildSpec>,
+    #[serde(default)]
+    container_env: BTreeMap<String, String>,
+    #[serde(default)]
+    mounts: Vec<Value>,
+    post_create_command: Option<Value>,
+    #[serde(default)]
+    forward_ports: Vec<Value>,
+    #[serde(default)]
+    features: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BuildSpec {
+    dockerfile: Option<String>,
+    context: Option<String>,
+    #[serde(default)]
+    args: BTreeMap<String, String>,
+}
+
+/// A command to run once after the container is created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Run through a shell
+    Shell(String),
+    /// Run directly, without a shell
+    Exec(Vec<String>),
+}
+
+/// Where the session's base image comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base {
+    Image(String),
+    Dockerfile {
+        dockerfile: PathBuf,
+        context: PathBuf,
+        args: BTreeMap<String, String>,
+    },
+}
+
+/// A project's devcontainer.json, resolved against the project directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevContainer {
+    pub path: PathBuf,
+    pub base: Base,
+    pub env: BTreeMap<String, String>,
+    /// `-v` specs
+    pub mounts: Vec<String>,
+    pub post_create: Vec<Command>,
+    /// `-p` specs
+    pub ports: Vec<String>,
+}
+
+impl DevContainer {
+    /// Parse devcontainer.json content; `path` is the file, `project` the workspace folder
+    pub fn parse(content: &str, path: &Path, project: &Path) -> Result<Self> {
+        let spec: Spec = serde_json::from_str(&strip_jsonc(content))
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let vars = Variables::new(project);
+
+        if !spec.features.is_empty() {
+            eprintln!(
+                "warning: devcontainer features are not supported, ignoring: {}",
+                spec.features.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+
+        let base = match (spec.image, spec.build) {
+            (_, Some(build)) if build.dockerfile.is_some() => {
+                let dockerfile = dir.join(build.dockerfile.unwrap_or_default());
+                let context = dir.join(build.context.as_deref().unwrap_or("."));
+                let args = build
+                    .args
+                    .into_iter()
+                    .map(|(key, value)| (key, vars.expand(&value)))
+                    .collect();
+                Base::Dockerfile {
+                    dockerfile,
+                    context,
+                    args,
+                }
+            }
+            (Some(image), _) => Base::Image(vars.expand(&image)),
+            _ => bail!(
+                "{} has neither \"image\" nor \"build.dockerfile\"\n\
+                 (docker compose based devcontainers are not supported)",
+                path.display()
+            ),
+        };
+
+        let env = spec
+            .container_env
+            .into_iter()
+            .map(|(key, value)| (key, vars.expand(&value)))
+            .collect();
+
+        let mounts = spec
+            .mounts
+            .iter()
+            .map(|mount| parse_mount(mount, &vars))
+            .collect::<Result<_>>()?;
+
+        let post_create = match spec.post_create_command {
+            None => Vec::new(),
+            // The object form names commands that devcontainers run in parallel
+            Some(Value::Object(commands)) => commands
+                .values()
+                .map(parse_command)
+                .collect::<Result<_>>()?,
+            Some(command) => vec![parse_command(&command)?],
+        };
+
+        let ports = spec
+            .forward_ports
+            .iter()
+            .map(|port| match port {
+                Value::Number(port) => Ok(format!("{}:{}", port, port)),
+                Value::String(port) if port.parse::<u16>().is_ok() => {
+                    Ok(format!("{}:{}", port, port))
+                }
+                other => bail!("unsupported forwardPorts entry: {}", other),
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DevContainer {
+            path: path.to_path_buf(),
+            base,
+            env,
+            mounts,
+            post_create,
+            ports,
+        })
+    }
+
+    /// The base image, building the devcontainer Dockerfile if needed
+    pub fn base_image(&self, runtime: Runtime) -> Result<String> {
+        let (dockerfile, context, args) = match &self.base {
+            Base::Image(image) => {
+                // Pull now so a missing image fails before the agent build starts
+                if image_id(runtime, image).is_err() {
+                    pull(runtime, image)?;
+                }
+                return Ok(image.clone());
+            }
+            Base::Dockerfile {
+                dockerfile,
+                context,
+                args,
+            } => (dockerfile, context, args),
+        };
+
+        let content = std::fs::read(dockerfile)
+            .with_context(|| format!("failed to read {}", dockerfile.display()))?;
+        let mut hasher = Sha256::new();
+        hasher.update(&content);
+        for (key, value) in args {
+            hasher.update(format!("{}={}\n", key, value));
+        }
+        let hash = format!("{:x}", hasher.finalize());
+        let reference = format!("{}:{}", BASE_REPOSITORY, &hash[..12]);
+        if image_id(runtime, &reference).is_ok() {
+            return Ok(reference);
+        }
+
+        eprintln!(
+            "{} Building devcontainer image from {}",
+            "→".cyan(),
+            dockerfile.display()
+        );
+        let mut cmd = runtime.command();
+        cmd.arg("build").arg("-t").arg(&reference);
+        for (key, value) in args {
+            cmd.arg("--build-arg").arg(format!("{}={}", key, value));
+        }
+        let output = cmd
+            .arg("-f")
+            .arg(dockerfile)
+            .arg(context)
+            .output()
+            .context("failed to start build command")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let tail: Vec<&str> = stderr.lines().rev().take(20).collect();
+            bail!(
+                "failed to build devcontainer image from {}:\n{}",
+                dockerfile.display(),
+                tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+            );
+        }
+
+        Ok(reference)
+    }
+
+    /// `-e`, `-p` and `-v` arguments for the session container
+    pub fn run_args(&self) -> Vec<String> {
+        let mut args = self.env_and_port_args();
+        for mount in &self.mounts {
+            args.push("-v".to_string());
+            args.push(mount.clone());
+        }
+        args
+    }
+
+    /// `-e` and `-p` arguments for the session container
+    pub fn env_and_port_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for (key, value) in &self.env {
+            args.push("-e".to_string());
+            args.push(format!("{}={}", key, value));
+        }
+        for port in &self.ports {
+            args.push("-p".to_string());
+            args.push(port.clone());
+        }
+        args
+    }
+
+    /// Run postCreateCommand in a freshly created container
+    pub fn run_post_create(&self, runtime: Runtime, container: &str, workdir: &str) -> Result<()> {
+        for command in &self.post_create {
+            let mut cmd = runtime.command();
+            cmd.args(["exec", "-w", workdir, container]);
+            match command {
+                Command::Shell(script) => {
+                    eprintln!("{} postCreateCommand: {}", "→".cyan(), script);
+                    cmd.args(["bash", "-lc", script]);
+                }
+                Command::Exec(argv) => {
+                    eprintln!("{} postCreateCommand: {}", "→".cyan(), argv.join(" "));
+                    cmd.args(argv);
+                }
+            }
+
+            let status = cmd.status().context("failed to run postCreateCommand")?;
+            if !status.success() {
+                bail!("postCreateCommand failed ({})", status);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Find and parse the project's devcontainer.json, if any
+pub fn find(project: &Path) -> Result<Option<DevContainer>> {
+    let candidates = [
+        project.join(".devcontainer").join("devcontainer.json"),
+        project.join(".devcontainer.json"),
+    ];
+    for path in candidates {
+        if path.is_file() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            return DevContainer::parse(&content, &path, project).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Project directory a devcontainer.json belongs to
+pub fn project_dir(path: &Path) -> PathBuf {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    if dir.ends_with(".devcontainer") {
+        dir.parent().unwrap_or(dir).to_path_buf()
+    } else {
+        dir.to_path_buf()
+    }
+}
+
+fn pull(runtime: Runtime, image: &str) -> Result<()> {
+    eprintln!("{} Pulling {}", "→".cyan(), image);
+    let status = runtime
+        .command()
+        .args(["pull", image])
+        .status()
+        .context("failed to pull image")?;
+    if !status.success() {
+        bail!("failed to pull devcontainer image {}", image);
+    }
+    Ok(())
+}
+
+/// `${...}` variables devcontainer.json values may use
+struct Variables {
+    project: String,
+    basename: String,
+}
+
+impl Variables {
+    fn new(project: &Path) -> Self {
+        Variables {
+            project: project.display().to_string(),
+            basename: project
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn expand(&self, value: &str) -> String {
+        let mut out = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            out.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                rest = &rest[start..];
+                break;
+            };
+            let name = &rest[start + 2..start + end];
+            out.push_str(&self.lookup(name));
+            rest = &rest[start + end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn lookup(&self, name: &str) -> String {
+        // ${localEnv:NAME:default}
+        if let Some(var) = name.strip_prefix("localEnv:") {
+            let (var, default) = var.split_once(':').unwrap_or((var, ""));
+            return env::var(var).unwrap_or_else(|_| default.to_string());
+        }
+        match name {
+            "localWorkspaceFolder" => self.project.clone(),
+            "localWorkspaceFolderBasename" | "containerWorkspaceFolderBasename" => {
+                self.basename.clone()
+            }
+            "containerWorkspaceFolder" => CONTAINER_WORKSPACE.to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Convert a devcontainer mount (string or object form) to a `-v` spec
+fn parse_mount(mount: &Value, vars: &Variables) -> Result<String> {
+    let fields: BTreeMap<String, String> = match mount {
+        // Flags such as "readonly" come without a value
+        Value::String(spec) => spec
+            .split(',')
+            .map(|field| field.split_once('=').unwrap_or((field, "")))
+            .map(|(key, value)| (key.trim().to_lowercase(), vars.expand(value.trim())))
+            .collect(),
+        Value::Object(object) => object
+            .iter()
+            .filter_map(|(key, value)| {
+                let value = match value {
+                    Value::Bool(flag) => flag.to_string(),
+                    other => vars.expand(other.as_str()?),
+                };
+                Some((key.to_lowercase(), value))
+            })
+            .collect(),
+        other => bail!("unsupported mounts entry: {}", other),
+    };
+
+    let source = fields.get("source").or_else(|| fields.get("src"));
+    let target = fields
+        .get("target")
+        .or_else(|| fields.get("destination"))
+        .or_else(|| fields.get("dst"));
+    let (Some(source), Some(target)) = (source, target) else {
+        bail!("mounts entry needs a source and a target: {}", mount);
+    };
+
+    let mut options = Vec::new();
+    match fields.get("type").map(String::as_str).unwrap_or("volume") {
+        // Shared label: the same host directory may be mounted into several sessions
+        "bind" if relabel_allowed(Path::new(source)) => options.push("z"),
+        "bind" | "volume" => {}
+        other => bail!(
+            "unsupported mount type '{}' (expected bind or volume)",
+            other
+        ),
+    }
+    let readonly = ["readonly", "ro"].iter().any(|key| {
+        fields
+            .get(*key)
+            .is_some_and(|v| v.is_empty() || v == "true" || v == "1")
+    });
+    if readonly {
+        options.push("ro");
+    }
+
+    if options.is_empty() {
+        Ok(format!("{}:{}", source, target))
+    } else {
+        Ok(format!("{}:{}:{}", source, target, options.join(",")))
+    }
+}
+
+/// Host paths never relabelled for SELinux: doing so breaks the host
+const SYSTEM_PATHS: [&str; 12] = [
+    "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/run", "/sbin", "/sys", "/usr",
+    "/var/run",
+];
+
+/// Whether a bind mount source may be relabelled: not a socket, a system path or $HOME itself
+fn relabel_allowed(source: &Path) -> bool {
+    if source == Path::new("/") || SYSTEM_PATHS.iter().any(|dir| source.starts_with(dir)) {
+        return false;
+    }
+    if env::var_os("HOME").is_some_and(|home| source == Path::new(&home)) {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        if std::fs::metadata(source).is_ok_and(|m| m.file_type().is_socket()) {
+            return false;
+        }
+    }
+    true
+}
+
+fn parse_command(command: &Value) -> Result<Command> {
+    match command {
+        Value::String(script) => Ok(Command::Shell(script.clone())),
+        Value::Array(argv) => argv
+            .iter()
+            .map(|arg| arg.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .map(Command::Exec)
+            .context("postCreateCommand arrays must contain only strings"),
+        other => bail!("unsupported postCreateCommand: {}", other),
+    }
+}
+
+/// Remove JSONC comments and trailing commas so serde_json can parse devcontainer.json
+fn strip_jsonc(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            (',', _) => {
+                // Drop the comma if only whitespace or comments separate it from a closer
+                let rest: String = chars.clone().collect();
+                let next = strip_leading_comments(&rest);
+                if !next.starts_with(['}', ']']) {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Skip whitespace and comments at the start of a JSONC fragment
+fn strip_leading_comments(mut rest: &str) -> &str {
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("//") {
+            rest = after.split_once('\n').map_or("", |(_, r)| r);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.split_once("*/").map_or("", |(_, r)| r);
+        } else {
+            return rest;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVCONTAINER: &str = r#"{
+        // Python toolchain shared with the team
+        "name": "webapp",
+        "build": { "dockerfile": "Dockerfile", "args": { "PY": "3.12" } },
+        "containerEnv": { "DATABASE_URL": "postgres://db/${localWorkspaceFolderBasename}" },
+        "mounts": [
+            "source=${localWorkspaceFolder}/data,target=/data,type=bind",
+            { "source": "webapp-node-modules", "target": "${containerWorkspaceFolder}/node_modules", "type": "volume" },
+        ],
+        /* ports the app listens on */
+        "forwardPorts": [8000, "5173"],
+        "postCreateCommand": "pip install -r requirements.txt // not a comment",
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let project = Path::new("/home/user/webapp");
+        let path = project.join(".devcontainer/devcontainer.json");
+        let dev = DevContainer::parse(DEVCONTAINER, &path, project).unwrap();
+
+        assert_eq!(
+            dev.base,
+            Base::Dockerfile {
+                dockerfile: project.join(".devcontainer/Dockerfile"),
+                context: project.join(".devcontainer/."),
+                args: BTreeMap::from([("PY".to_string(), "3.12".to_string())]),
+            }
+        );
+        assert_eq!(dev.env["DATABASE_URL"], "postgres://db/webapp");
+        assert_eq!(
+            dev.mounts,
+            vec![
+                "/home/user/webapp/data:/data:z".to_string(),
+                "webapp-node-modules:/workspace/node_modules".to_string(),
+            ]
+        );
+        assert_eq!(dev.ports, vec!["8000:8000", "5173:5173"]);
+        assert_eq!(
+            dev.post_create,
+            vec![Command::Shell(
+                "pip install -r requirements.txt // not a comment".to_string()
+            )]
+        );
+        assert_eq!(
+            dev.run_args()[..4],
+            ["-e", "DATABASE_URL=postgres://db/webapp", "-p", "8000:8000"]
+        );
+    }
+
+    #[test]
+    fn test_parse_mount_labels() {
+        let vars = Variables::new(Path::new("/p"));
+        let mount = |spec: &str| parse_mount(&Value::String(spec.to_string()), &vars).unwrap();
+
+        assert_eq!(
+            mount("source=/p/data,target=/data,type=bind"),
+            "/p/data:/data:z"
+        );
+        assert_eq!(
+            mount("source=/p/data,target=/data,type=bind,readonly"),
+            "/p/data:/data:z,ro"
+        );
+        assert_eq!(
+            mount("source=/var/run/docker.sock,target=/var/run/docker.sock,type=bind"),
+            "/var/run/docker.sock:/var/run/docker.sock"
+        );
+        assert_eq!(
+            mount("source=/etc/hosts,target=/etc/hosts,type=bind"),
+            "/etc/hosts:/etc/hosts"
+        );
+
+        let object = serde_json::json!({ "source": "cache", "target": "/cache", "readonly": true });
+        assert_eq!(parse_mount(&object, &vars).unwrap(), "cache:/cache:ro");
+    }
+
+    #[test]
+    fn test_parse_image() {
+        let content = r#"{ "image": "mcr.microsoft.com/devcontainers/rust:1", "postCreateCommand": ["cargo", "fetch"] }"#;
+        let dev = DevContainer::parse(content, Path::new("/p/.devcontainer.json"), Path::new("/p"))
+            .unwrap();
+        assert_eq!(
+            dev.base,
+            Base::Image("mcr.microsoft.com/devcontainers/rust:1".to_string())
+        );
+        assert_eq!(
+            project_dir(Path::new("/p/.devcontainer.json")),
+            Path::new("/p")
+        );
+        assert_eq!(
+            project_dir(Path::new("/p/.devcontainer/devcontainer.json")),
+            Path::new("/p")
+        );
+        assert_eq!(
+            dev.post_create,
+            vec![Command::Exec(vec![
+                "cargo".to_string(),
+                "fetch".to_string()
+            ])]
+        );
+
+        let compose = r#"{ "dockerComposeFile": "compose.yml", "service": "app" }"#;
+        assert!(
+            DevContainer::parse(compose, Path::new("/p/.devcontainer.json"), Path::new("/p"))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::get_state_home;
+use crate::git;
+use crate::headless::EXIT_CODE_FILE;
+use crate::snapshot::is_valid_name;
+use crate::time::{format_compact, unix_now};
+
+/// Container label tying a session to the fan-out that created it
+pub const FANOUT_LABEL: &str = "klotho.fanout";
+
+/// A set of sessions working on the same prompt in parallel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fanout {
+    pub id: String,
+    /// Top level of the repository the worktrees branch from
+    pub repo: String,
+    /// Commit every run started from
+    pub base: String,
+    /// Unix timestamp the fan-out was started at
+    pub created: u64,
+    pub runs: Vec<FanoutRun>,
+}
+
+/// One agent session within a fan-out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanoutRun {
+    pub session: String,
+    pub agent: String,
+    pub branch: String,
+    pub worktree: String,
+}
+
+/// Progress of a fan-out run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunState {
+    /// Agent is still working
+    Running,
+    /// Agent exited with this code
+    Finished(i32),
+}
+
+impl Fanout {
+    /// Load a fan-out manifest by ID
+    pub fn load(id: &str) -> Result<Self> {
+        let path = fanout_dir(id).join("fanout.toml");
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("fan-out '{}' not found", id))?;
+        toml::from_str(&content).context("failed to parse fan-out manifest")
+    }
+
+    /// Load the most recently created fan-out
+    pub fn latest() -> Result<Self> {
+        list()?
+            .pop()
+            .context("no fan-outs found\nstart one with: klotho fanout --prompt-file FILE PATH")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = fanout_dir(&self.id);
+        fs::create_dir_all(&dir).context("failed to create fan-out directory")?;
+
+        let content = toml::to_string(self).context("failed to serialize fan-out")?;
+        fs::write(dir.join("fanout.toml"), content).context("failed to write fan-out manifest")?;
+        Ok(())
+    }
+
+    /// Read the state of a run from its task directory
+    pub fn run_state(&self, run: &FanoutRun) -> RunState {
+        let path = run_dir(&self.id, &run.session).join(EXIT_CODE_FILE);
+        match fs::read_to_string(path) {
+            Ok(code) => RunState::Finished(code.trim().parse().unwrap_or(-1)),
+            Err(_) => RunState::Running,
+        }
+    }
+
+    /// Count commits a run's branch has on top of the base
+    pub fn commits(&self, run: &FanoutRun) -> Result<usize> {
+        let range = format!("{}..{}", self.base, run.branch);
+        let count = git::git(Path::new(&self.repo), &["rev-list", "--count", &range])?;
+        count.parse().context("failed to count commits")
+    }
+
+    /// Summarize a run's changes against the base (git diff --shortstat)
+    pub fn shortstat(&self, run: &FanoutRun) -> Result<String> {
+        git::git(
+            Path::new(&self.repo),
+            &["diff", "--shortstat", &self.base, &run.branch],
+        )
+    }
+}
+
+/// Get the directory holding a fan-out's manifest and task directories
+pub fn fanout_dir(id: &str) -> PathBuf {
+    get_state_home().join("fanout").join(id)
+}
+
+/// Get the host task directory mounted into a run's container
+pub fn run_dir(id: &str, session: &str) -> PathBuf {
+    fanout_dir(id).join(session)
+}
+
+/// Generate an ID for a new fan-out
+pub fn new_id() -> String {
+    format!("fanout-{}", format_compact(unix_now()))
+}
+
+/// Validate a fan-out ID before it becomes part of paths, branches and container names
+pub fn validate_id(id: &str) -> Result<()> {
+    if !is_valid_name(id) {
+        bail!(
+            "invalid fan-out ID '{}' - use letters, digits, '-', '_' and '.'",
+            id
+        );
+    }
+    Ok(())
+}
+
+/// List fan-outs, oldest first
+pub fn list() -> Result<Vec<Fanout>> {
+    let dir = get_state_home().join("fanout");
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut fanouts = Vec::new();
+    for entry in fs::read_dir(&dir).context("failed to read fan-out directory")? {
+        let path = entry?.path().join("fanout.toml");
+        if let Ok(content) = fs::read_to_string(&path) {
+            match toml::from_str::<Fanout>(&content) {
+                Ok(fanout) => fanouts.push(fanout),
+                Err(e) => eprintln!(
+                    "warning: skipping invalid fan-out {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    }
+
+    fanouts.sort_by(|a, b| a.created.cmp(&b.created).then(a.id.cmp(&b.id)));
+    Ok(fanouts)
+}
+
+/// Session name for the n-th run of an agent in a fan-out
+pub fn session_name(id: &str, agent: &str, n: usize) -> String {
+    format!("{}-{}-{}", id, agent, n)
+}
+
+/// Branch name for the n-th run of an agent in a fan-out
+pub fn branch_name(id: &str, agent: &str, n: usize) -> String {
+    format!("klotho/{}/{}-{}", id, agent, n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(session_name("fo", "claude", 2), "fo-claude-2");
+        assert_eq!(branch_name("fo", "claude", 2), "klotho/fo/claude-2");
+    }
+
+    #[test]
+    fn test_validate_id() {
+        assert!(validate_id(&new_id()).is_ok());
+        assert!(validate_id("refactor-1").is_ok());
+        assert!(validate_id("../x").is_err());
+        assert!(validate_id("a/b").is_err());
+        assert!(validate_id("").is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Build a git command running in a directory
+pub fn command(dir: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir);
+    cmd
+}
+
+/// Run a prepared git command, returning trimmed stdout
+pub fn run(mut cmd: Command, args: &[&str]) -> Result<String> {
+    cmd.args(args);
+
+    let output = cmd.output().context("failed to run git")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git {} failed: {}", args.join(" "), stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run git in a directory, returning trimmed stdout
+pub fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    run(command(dir), args)
+}
+
+/// Get the top level of the work tree containing a directory
+pub fn toplevel(dir: &Path) -> Result<String> {
+    git(dir, &["rev-parse", "--show-toplevel"])
+}
+
+/// Check if a directory is the top level of a git work tree
+pub fn is_toplevel(dir: &Path) -> bool {
+    let top = match toplevel(dir) {
+        Ok(top) => top,
+        Err(_) => return false,
+    };
+
+    match (Path::new(&top).canonicalize(), dir.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Check if a work tree has uncommitted changes (including untracked files)
+pub fn is_dirty(dir: &Path) -> Result<bool> {
+    Ok(!git(dir, &["status", "--porcelain"])?.is_empty())
+}
+
+/// Write the whole work tree (tracked and untracked, minus ignored) as a tree object
+///
+/// Stages into a private index so the user's index is left untouched.
+pub fn write_worktree_tree(dir: &Path) -> Result<String> {
+    let index_name = format!("klotho-index-{}", std::process::id());
+    let index_path = PathBuf::from(git(dir, &["rev-parse", "--git-path", &index_name])?);
+    let index_path = if index_path.is_absolute() {
+        index_path
+    } else {
+        dir.join(index_path)
+    };
+
+    let with_index = |args: &[&str]| {
+        let mut cmd = command(dir);
+        cmd.env("GIT_INDEX_FILE", &index_path);
+        run(cmd, args)
+    };
+
+    let result = (|| -> Result<String> {
+        if git(dir, &["rev-parse", "--verify", "-q", "HEAD"]).is_ok() {
+            with_index(&["read-tree", "HEAD"])?;
+        }
+        with_index(&["add", "-A"])?;
+        with_index(&["write-tree"])
+    })();
+
+    let _ = std::fs::remove_file(&index_path);
+    result
+}
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+
+use crate::agent::AgentConfig;
+
+/// Where the host task directory is mounted inside the container
+///
+/// Headless runs write `transcript.log` and `exit_code` here.
+pub const CONTAINER_TASK_DIR: &str = "/home/agent/.klotho/task";
+
+/// Transcript file name inside the task directory
+pub const TRANSCRIPT_FILE: &str = "transcript.log";
+
+/// Exit code file name inside the task directory
+pub const EXIT_CODE_FILE: &str = "exit_code";
+
+/// Get an agent's headless command, with a hint if it isn't configured
+pub fn headless_cmd(config: &AgentConfig) -> Result<&str> {
+    config.headless_cmd.as_deref().ok_or_else(|| {
+        anyhow::anyhow!(
+            "agent '{}' has no AGENT_HEADLESS_CMD configured\n\
+             add it to ~/.config/klotho/agents/{}/config.conf",
+            config.name,
+            config.name
+        )
+    })
+}
+
+/// Read the prompt from --prompt or --prompt-file
+pub fn read_prompt(prompt: Option<String>, prompt_file: Option<String>) -> Result<String> {
+    let prompt = match (prompt, prompt_file) {
+        (Some(_), Some(_)) => anyhow::bail!("use either --prompt or --prompt-file, not both"),
+        (Some(p), None) => p,
+        (None, Some(file)) => std::fs::read_to_string(&file)
+            .with_context(|| format!("failed to read prompt file: {}", file))?,
+        (None, None) => anyhow::bail!("a prompt is required: use --prompt or --prompt-file"),
+    };
+
+    if prompt.trim().is_empty() {
+        anyhow::bail!("prompt is empty");
+    }
+    Ok(prompt)
+}
+
+/// Build `exec`/`run` arguments passing the prompt and headless command as environment
+pub fn exec_env_args(config: &AgentConfig, prompt: &str) -> Result<Vec<String>> {
+    Ok(vec![
+        "-e".to_string(),
+        format!("KLOTHO_PROMPT={}", prompt),
+        "-e".to_string(),
+        format!("AGENT_HEADLESS_CMD={}", headless_cmd(config)?),
+    ])
+}
+
+/// Bash script that runs the headless command in /workspace and records the result
+///
+/// With `commit_message`, whatever the agent left in the work tree is committed
+/// afterwards so results can be compared as branches.
+pub fn task_script(config: &AgentConfig, commit_message: Option<&str>) -> String {
+    let mut script = vec!["set -o pipefail".to_string()];
+
+    if let Some(exports) = config.env_export_snippet() {
+        script.push(exports);
+    }
+
+    script.push("cd /workspace".to_string());
+    script.push(format!(
+        "bash -c \"$AGENT_HEADLESS_CMD\" < /dev/null 2>&1 | tee {}/{}",
+        CONTAINER_TASK_DIR, TRANSCRIPT_FILE
+    ));
+    script.push("code=$?".to_string());
+
+    if let Some(message) = commit_message {
+        script.push(format!(
+            "git add -A && git -c user.name=klotho -c user.email=klotho@localhost \
+             commit -q -m {} >/dev/null 2>&1",
+            shell_quote(message)
+        ));
+    }
+
+    script.push(format!(
+        "echo \"$code\" > {}/{}",
+        CONTAINER_TASK_DIR, EXIT_CODE_FILE
+    ));
+    script.push("exit \"$code\"".to_string());
+
+    script.join("\n")
+}
+
+/// Quote a string for bash using single quotes
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
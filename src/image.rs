@@ -0,0 +1,585 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+use crate::container::Runtime;
+use crate::derived::BASE_ID_LABEL;
+use crate::selection::Session;
+use crate::time::{parse_timestamp, unix_now};
+
+/// Image label set on every image klotho builds
+pub const IMAGE_LABEL: &str = "klotho";
+
+/// Image label recording which agent an image was built for
+pub const AGENT_LABEL: &str = "klotho.agent";
+
+/// Image label with the agent version found in the image after install
+pub const AGENT_VERSION_LABEL: &str = "klotho.agent.version";
+
+/// Container label recording the exact image ID a session was created from
+pub const IMAGE_ID_LABEL: &str = "klotho.image.id";
+
+/// Container label recording the image tag a session was pinned to
+pub const IMAGE_TAG_LABEL: &str = "klotho.image.tag";
+
+/// Tag prefix of images derived from a project Containerfile
+pub const PROJECT_TAG_PREFIX: &str = "project-";
+
+/// Tag prefix of agent images built on a project's devcontainer image
+pub const DEVCONTAINER_TAG_PREFIX: &str = "devcontainer-";
+
+/// A klotho image known to the runtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    /// Full image ID without the "sha256:" prefix
+    pub id: String,
+    /// Repository and tag (e.g. "klotho-claude:latest"), `None` when dangling
+    pub name: Option<String>,
+    pub agent: Option<String>,
+    /// Size as reported by the runtime
+    pub size: String,
+    /// Unix timestamp the image was built at
+    pub created: Option<u64>,
+    /// Built under the pre-klotho `agent-session-<agent>` naming
+    pub legacy: bool,
+    /// Agent version recorded at build time
+    pub agent_version: Option<String>,
+}
+
+impl Image {
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(12)]
+    }
+
+    /// Name for display, the short ID for dangling images
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("<none> ({})", self.short_id()),
+        }
+    }
+
+    /// Built for one project: from its Containerfile or on its devcontainer image
+    pub fn is_derived(&self) -> bool {
+        self.name
+            .as_deref()
+            .and_then(|name| name.split_once(':'))
+            .is_some_and(|(_, tag)| is_project_tag(tag))
+    }
+
+    /// The build version tag (e.g. "20240115-103000"), `None` for latest, project and legacy images
+    pub fn version_tag(&self) -> Option<&str> {
+        if self.legacy {
+            return None;
+        }
+        let (_, tag) = self.name.as_deref()?.split_once(':')?;
+        (tag != "latest" && !is_project_tag(tag)).then_some(tag)
+    }
+
+    /// Reference to pass to `rmi`: the tag, or the ID for dangling images
+    pub fn reference(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+}
+
+/// List klotho images: labelled builds plus `klotho-*` and legacy `agent-session-*` tags
+pub fn list_images(runtime: Runtime) -> Result<Vec<Image>> {
+    let output = runtime
+        .command()
+        .args([
+            "images",
+            "--no-trunc",
+            "--format",
+            "{{.ID}}|{{.Repository}}|{{.Tag}}|{{.Size}}|{{.CreatedAt}}",
+        ])
+        .output()
+        .context("failed to list images")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to list images: {}", stderr);
+    }
+
+    let labelled = labelled_images(runtime)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut images: Vec<Image> = stdout
+        .lines()
+        .filter_map(|line| parse_image_line(line, &labelled))
+        .collect();
+
+    images.sort_by(|a, b| {
+        a.name
+            .is_none()
+            .cmp(&b.name.is_none())
+            .then(a.name.cmp(&b.name))
+    });
+    Ok(images)
+}
+
+/// Labels of an image carrying the klotho label
+#[derive(Debug, Clone, Default)]
+struct Labelled {
+    agent: Option<String>,
+    agent_version: Option<String>,
+}
+
+/// Map IDs of images carrying the klotho label to their agent and version labels
+fn labelled_images(runtime: Runtime) -> Result<HashMap<String, Labelled>> {
+    let output = runtime
+        .command()
+        .args([
+            "images",
+            "--all",
+            "--no-trunc",
+            "--filter",
+            &format!("label={}=true", IMAGE_LABEL),
+            "--format",
+            &format!(
+                "{{{{.ID}}}}|{{{{index .Labels \"{}\"}}}}|{{{{index .Labels \"{}\"}}}}",
+                AGENT_LABEL, AGENT_VERSION_LABEL
+            ),
+        ])
+        .output()
+        .context("failed to list images")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to list images: {}", stderr);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split('|');
+            let id = normalize_id(fields.next()?);
+            let mut label = || {
+                let value = fields.next().unwrap_or("").trim();
+                (!value.is_empty() && value != "<no value>").then(|| value.to_string())
+            };
+            let agent = label();
+            let agent_version = label();
+            Some((
+                id,
+                Labelled {
+                    agent,
+                    agent_version,
+                },
+            ))
+        })
+        .collect())
+}
+
+/// Parse one `images --format` line, skipping images that aren't klotho's
+fn parse_image_line(line: &str, labelled: &HashMap<String, Labelled>) -> Option<Image> {
+    let mut fields = line.trim().splitn(5, '|');
+    let id = normalize_id(fields.next()?);
+    let repository = fields.next()?.trim();
+    let tag = fields.next()?.trim();
+    let size = fields.next()?.trim().to_string();
+    let created = parse_timestamp(fields.next().unwrap_or(""));
+
+    // podman qualifies local images with "localhost/"
+    let repository = repository.strip_prefix("localhost/").unwrap_or(repository);
+    let name =
+        (repository != "<none>" && tag != "<none>").then(|| format!("{}:{}", repository, tag));
+
+    let (agent, legacy) = if let Some(agent) = repository.strip_prefix("klotho-") {
+        (Some(agent.to_string()), false)
+    } else if let Some(agent) = repository.strip_prefix("agent-session-") {
+        (Some(agent.to_string()), true)
+    } else if let Some(labels) = labelled.get(&id) {
+        (labels.agent.clone(), false)
+    } else {
+        return None;
+    };
+    let agent_version = labelled
+        .get(&id)
+        .and_then(|labels| labels.agent_version.clone());
+
+    Some(Image {
+        id,
+        name,
+        agent,
+        size,
+        created,
+        legacy,
+        agent_version,
+    })
+}
+
+/// Strip the "sha256:" prefix from an image ID
+fn normalize_id(id: &str) -> String {
+    let id = id.trim();
+    id.strip_prefix("sha256:").unwrap_or(id).to_string()
+}
+
+/// Whether a tag names a per-project image rather than a build version
+pub fn is_project_tag(tag: &str) -> bool {
+    tag.starts_with(PROJECT_TAG_PREFIX) || tag.starts_with(DEVCONTAINER_TAG_PREFIX)
+}
+
+/// Image reference for an agent at a tag, e.g. "klotho-claude:20240115-103000"
+pub fn image_ref(agent: &str, tag: &str) -> String {
+    format!("klotho-{}:{}", agent, tag)
+}
+
+/// Get the full ID (without "sha256:") of an image reference
+pub fn image_id(runtime: Runtime, reference: &str) -> Result<String> {
+    let output = runtime
+        .command()
+        .args(["image", "inspect", "--format", "{{.Id}}", reference])
+        .output()
+        .context("failed to inspect image")?;
+
+    if !output.status.success() {
+        anyhow::bail!("image {} not found", reference);
+    }
+
+    Ok(normalize_id(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Seconds since an image was built, `None` if it is missing or the runtime doesn't say
+pub fn image_age(runtime: Runtime, reference: &str) -> Option<u64> {
+    let output = runtime
+        .command()
+        .args(["image", "inspect", "--format", "{{.Created}}", reference])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let created = parse_timestamp(&String::from_utf8_lossy(&output.stdout))?;
+    Some(unix_now().saturating_sub(created))
+}
+
+/// Get the labels baked into an image
+pub fn image_labels(runtime: Runtime, reference: &str) -> Result<HashMap<String, String>> {
+    let output = runtime
+        .command()
+        .args([
+            "image",
+            "inspect",
+            "--format",
+            "{{json .Config.Labels}}",
+            reference,
+        ])
+        .output()
+        .context("failed to inspect image")?;
+
+    if !output.status.success() {
+        anyhow::bail!("image {} not found", reference);
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let labels: Option<HashMap<String, String>> =
+        serde_json::from_str(stdout.trim()).context("failed to parse image labels")?;
+    Ok(labels.unwrap_or_default())
+}
+
+/// List an agent's versioned tags (everything but "latest" and project images), newest first
+pub fn version_tags(runtime: Runtime, agent: &str) -> Result<Vec<String>> {
+    let prefix = format!("klotho-{}:", agent);
+    let mut tags: Vec<String> = list_images(runtime)?
+        .into_iter()
+        .filter_map(|image| image.name?.strip_prefix(&prefix).map(str::to_string))
+        .filter(|tag| tag != "latest" && !is_project_tag(tag))
+        .collect();
+
+    // Build tags are timestamps, so they sort chronologically
+    tags.sort_by(|a, b| b.cmp(a));
+    tags.dedup();
+    Ok(tags)
+}
+
+/// Versioned builds beyond the newest `keep` of each agent, oldest last
+pub fn stale_versions(images: &[Image], keep: usize) -> Vec<&Image> {
+    let mut versions: Vec<&Image> = images
+        .iter()
+        .filter(|i| i.version_tag().is_some())
+        .collect();
+
+    // Build tags are timestamps, so they sort chronologically
+    versions.sort_by(|a, b| (&a.agent, b.version_tag()).cmp(&(&b.agent, a.version_tag())));
+
+    let mut stale = Vec::new();
+    let mut kept: HashMap<&Option<String>, usize> = HashMap::new();
+    for image in versions {
+        let count = kept.entry(&image.agent).or_default();
+        if *count < keep {
+            *count += 1;
+        } else {
+            stale.push(image);
+        }
+    }
+    stale
+}
+
+/// Resolve an agent's image at a tag, listing the available tags if it is missing
+pub fn resolve_tag(runtime: Runtime, agent: &str, tag: &str) -> Result<String> {
+    let reference = image_ref(agent, tag);
+    if image_id(runtime, &reference).is_ok() {
+        return Ok(reference);
+    }
+
+    let tags = version_tags(runtime, agent)?;
+    if tags.is_empty() {
+        anyhow::bail!(
+            "image {} not found\nno versioned builds of '{}' exist yet: klotho build {}",
+            reference,
+            agent,
+            agent
+        );
+    }
+    anyhow::bail!(
+        "image {} not found\navailable tags: {}",
+        reference,
+        tags.join(", ")
+    );
+}
+
+/// Run a shell script in a throwaway container of an image and return its output
+///
+/// Runs as the image's user, bypassing the entrypoint.
+pub fn run_in_image(runtime: Runtime, reference: &str, script: &str) -> Result<String> {
+    let output = runtime
+        .command()
+        .args([
+            "run",
+            "--rm",
+            "--entrypoint",
+            "/bin/sh",
+            reference,
+            "-c",
+            script,
+        ])
+        .output()
+        .context("failed to start container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("`{}` failed in {}: {}", script, reference, stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Add a tag to an existing image
+pub fn tag_image(runtime: Runtime, source: &str, target: &str) -> Result<()> {
+    let output = runtime
+        .command()
+        .args(["tag", source, target])
+        .output()
+        .context("failed to tag image")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to tag {} as {}: {}", source, target, stderr.trim());
+    }
+
+    Ok(())
+}
+
+/// The image a session container runs, and the tag it was pinned to if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionImage {
+    pub id: String,
+    pub pinned: Option<String>,
+    /// Agent image a derived project image was built on
+    pub base: Option<String>,
+}
+
+impl SessionImage {
+    /// The agent image behind the session, looking through project images
+    pub fn agent_image_id(&self) -> &str {
+        self.base.as_deref().unwrap_or(&self.id)
+    }
+}
+
+/// Look up the image of each session container, keyed by container name
+pub fn session_images(
+    runtime: Runtime,
+    sessions: &[Session],
+) -> Result<HashMap<String, SessionImage>> {
+    if sessions.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let output = runtime
+        .command()
+        .args([
+            "inspect",
+            "--format",
+            &format!(
+                "{{{{.Image}}}}|{{{{index .Config.Labels \"{}\"}}}}|{{{{index .Config.Labels \"{}\"}}}}",
+                IMAGE_TAG_LABEL, BASE_ID_LABEL
+            ),
+        ])
+        .args(sessions.iter().map(|s| s.container.as_str()))
+        .output()
+        .context("failed to inspect containers")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to inspect containers: {}", stderr);
+    }
+
+    // inspect prints one line per container, in argument order
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(sessions
+        .iter()
+        .zip(stdout.lines())
+        .map(|(session, line)| {
+            let mut fields = line.split('|');
+            let id = fields.next().unwrap_or("");
+            let label = |value: Option<&str>| {
+                let value = value.unwrap_or("").trim();
+                (!value.is_empty() && value != "<no value>").then(|| value.to_string())
+            };
+            let image = SessionImage {
+                id: normalize_id(id),
+                pinned: label(fields.next()),
+                base: label(fields.next()),
+            };
+            (session.container.clone(), image)
+        })
+        .collect())
+}
+
+/// Map image IDs to the names of sessions whose containers use them
+pub fn image_users(runtime: Runtime, sessions: &[Session]) -> Result<HashMap<String, Vec<String>>> {
+    let images = session_images(runtime, sessions)?;
+
+    let mut users: HashMap<String, Vec<String>> = HashMap::new();
+    for session in sessions {
+        if let Some(image) = images.get(&session.container) {
+            users
+                .entry(image.id.clone())
+                .or_default()
+                .push(session.name.clone());
+        }
+    }
+    Ok(users)
+}
+
+/// Get the ID of an agent's latest image, if it has been built
+pub fn latest_id(runtime: Runtime, agent: &str) -> Option<String> {
+    image_id(runtime, &image_ref(agent, "latest")).ok()
+}
+
+/// Remove a tag, leaving the image in place when other images build on it
+///
+/// podman refuses to `rmi` the last tag of an image with children, so it untags.
+pub fn untag_image(runtime: Runtime, reference: &str) -> Result<()> {
+    let verb = match runtime {
+        Runtime::Podman => "untag",
+        Runtime::Docker => "rmi",
+    };
+    let output = runtime
+        .command()
+        .args([verb, reference])
+        .output()
+        .context("failed to untag image")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to untag {}: {}", reference, stderr.trim());
+    }
+
+    Ok(())
+}
+
+/// Remove an image by tag or ID
+pub fn remove_image(runtime: Runtime, reference: &str) -> Result<()> {
+    let output = runtime
+        .command()
+        .args(["rmi", reference])
+        .output()
+        .context("failed to remove image")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("failed to remove image {}: {}", reference, stderr.trim());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_versions() {
+        let image = |agent: &str, tag: &str| Image {
+            id: format!("{}-{}", agent, tag),
+            name: Some(image_ref(agent, tag)),
+            agent: Some(agent.to_string()),
+            size: "1 GB".to_string(),
+            created: None,
+            legacy: false,
+            agent_version: None,
+        };
+        let images = vec![
+            image("claude", "20240101-000000"),
+            image("claude", "latest"),
+            image("claude", "20240301-000000"),
+            image("claude", "project-abc"),
+            image("claude", "20240201-000000"),
+            image("opencode", "20240101-000000"),
+        ];
+
+        let stale: Vec<String> = stale_versions(&images, 2)
+            .iter()
+            .map(|image| image.display_name())
+            .collect();
+        assert_eq!(stale, vec!["klotho-claude:20240101-000000"]);
+        assert_eq!(stale_versions(&images, 0).len(), 4);
+    }
+
+    #[test]
+    fn test_parse_image_line() {
+        let labelled = HashMap::from([
+            (
+                "abc123".to_string(),
+                Labelled {
+                    agent: Some("claude".to_string()),
+                    agent_version: None,
+                },
+            ),
+            (
+                "def456".to_string(),
+                Labelled {
+                    agent: Some("claude".to_string()),
+                    agent_version: Some("1.0.51".to_string()),
+                },
+            ),
+        ]);
+
+        let image = parse_image_line(
+            "sha256:def456|localhost/klotho-claude|latest|1.2 GB|2024-01-15 10:30:00 +0000 UTC",
+            &labelled,
+        )
+        .unwrap();
+        assert_eq!(image.id, "def456");
+        assert_eq!(image.name.as_deref(), Some("klotho-claude:latest"));
+        assert_eq!(image.agent.as_deref(), Some("claude"));
+        assert_eq!(image.created, Some(1_705_314_600));
+        assert_eq!(image.agent_version.as_deref(), Some("1.0.51"));
+        assert!(!image.legacy);
+
+        let legacy =
+            parse_image_line("sha256:0a|agent-session-opencode|latest|900MB|", &labelled).unwrap();
+        assert!(legacy.legacy);
+        assert_eq!(legacy.agent.as_deref(), Some("opencode"));
+        assert_eq!(legacy.agent_version, None);
+
+        // Dangling images are only recognised by their label
+        let dangling = parse_image_line("sha256:abc123|<none>|<none>|1.1GB|", &labelled).unwrap();
+        assert_eq!(dangling.name, None);
+        assert_eq!(dangling.reference(), "abc123");
+        assert!(parse_image_line("sha256:fff|<none>|<none>|1MB|", &labelled).is_none());
+        assert!(parse_image_line("sha256:fff|debian|bookworm|1MB|", &labelled).is_none());
+    }
+}
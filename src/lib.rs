@@ -1,6 +1,22 @@
 pub mod agent;
+pub mod bundle;
+pub mod cache;
 pub mod cli;
 pub mod commands;
 pub mod config;
 pub mod container;
+pub mod customization;
+pub mod derived;
+pub mod devcontainer;
+pub mod fanout;
+pub mod git;
+pub mod headless;
+pub mod image;
+pub mod recording;
 pub mod resources;
+pub mod selection;
+pub mod snapshot;
+pub mod time;
+pub mod tools;
+pub mod volume;
+pub mod worktree;
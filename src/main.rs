@@ -1,7 +1,11 @@
 use anyhow::Result;
 use clap::Parser;
-use klotho::cli::{Cli, Commands};
+use klotho::cli::{
+    BuildArgs, CacheCommands, Cli, Commands, FanoutCommands, ImagesCommands, ProgressArg,
+    RecordingsCommands, SessionCommands, VolumesCommands,
+};
 use klotho::commands;
+use klotho::commands::build::{BuildRequest, Progress};
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -15,33 +19,216 @@ fn main() -> Result<()> {
     };
 
     match cli.command {
-        Commands::Start { agent, name, linked_dirs, paths } => {
-            commands::start::run(agent, name, linked_dirs, paths, runtime_override)?;
+        Commands::Start {
+            agent,
+            name,
+            linked_dirs,
+            record,
+            worktree,
+            ephemeral,
+            image_tag,
+            paths,
+        } => {
+            let options = commands::start::CreateOptions {
+                linked_dirs,
+                record,
+                worktree,
+                ephemeral,
+                image_tag,
+                layout: None,
+            };
+            commands::start::run(agent, name, paths, options, runtime_override)?;
             Ok(())
         }
-        Commands::Stop { name } => {
-            commands::stop::run(name, runtime_override)?;
+        Commands::Stop { force, select } => {
+            commands::stop::run(select.into(), force, runtime_override)?;
             Ok(())
         }
-        Commands::Restart { name } => {
-            commands::restart::run(name, runtime_override)?;
+        Commands::Restart { force, select } => {
+            commands::restart::run(select.into(), force, runtime_override)?;
+            Ok(())
+        }
+        Commands::Attach { read_only, name } => {
+            commands::attach::run(name, read_only, runtime_override)?;
             Ok(())
         }
         Commands::Ls => {
             commands::ls::run(runtime_override)?;
             Ok(())
         }
-        Commands::Rm { force, name } => {
-            commands::rm::run(name, force, runtime_override)?;
+        Commands::Inspect { name } => {
+            commands::inspect::run(name, runtime_override)?;
+            Ok(())
+        }
+        Commands::Rm { force, select } => {
+            commands::rm::run(select.into(), force, runtime_override)?;
+            Ok(())
+        }
+        Commands::Prune { older_than, force } => {
+            commands::prune::run(&older_than, force, runtime_override)?;
+            Ok(())
+        }
+        Commands::Recordings { command } => match command {
+            RecordingsCommands::Ls { name } => {
+                commands::recordings::run_ls(name)?;
+                Ok(())
+            }
+        },
+        Commands::Replay {
+            speed,
+            idle_limit,
+            id,
+            name,
+        } => {
+            commands::replay::run(name, id, speed, idle_limit)?;
             Ok(())
         }
-        Commands::Build { all, agents } => {
-            commands::build::run(all, agents, false, runtime_override)?;
+        Commands::Snapshot { list, name, label } => {
+            commands::snapshot::run(name, label, list, runtime_override)?;
             Ok(())
         }
-        Commands::Rebuild { all, agents } => {
-            commands::build::run(all, agents, true, runtime_override)?;
+        Commands::Rollback { force, name, label } => {
+            commands::rollback::run(name, label, force, runtime_override)?;
             Ok(())
         }
+        Commands::Run {
+            agent,
+            prompt,
+            prompt_file,
+            output,
+            path,
+        } => {
+            commands::run::run(agent, prompt, prompt_file, output, path, runtime_override)?;
+            Ok(())
+        }
+        Commands::Fanout { command, args } => match command {
+            Some(FanoutCommands::Status { id }) => {
+                commands::fanout::run_status(id, runtime_override)?;
+                Ok(())
+            }
+            Some(FanoutCommands::Diff { id, session }) => {
+                commands::fanout::run_diff(id, session)?;
+                Ok(())
+            }
+            None => {
+                commands::fanout::run(
+                    args.agents,
+                    args.count,
+                    args.prompt,
+                    args.prompt_file,
+                    args.id,
+                    args.path,
+                    runtime_override,
+                )?;
+                Ok(())
+            }
+        },
+        Commands::Upgrade {
+            image_tag,
+            force,
+            name,
+        } => {
+            commands::upgrade::run(name, image_tag, force, runtime_override)?;
+            Ok(())
+        }
+        Commands::RollbackImage { agent, tag } => {
+            commands::rollback_image::run(&agent, &tag, runtime_override)?;
+            Ok(())
+        }
+        Commands::Volumes { command } => match command {
+            VolumesCommands::Ls => {
+                commands::volumes::run_ls(runtime_override)?;
+                Ok(())
+            }
+            VolumesCommands::Rm { force, names } => {
+                commands::volumes::run_rm(names, force, runtime_override)?;
+                Ok(())
+            }
+        },
+        Commands::Cache { command } => match command {
+            CacheCommands::Ls => {
+                commands::cache::run_ls(runtime_override)?;
+                Ok(())
+            }
+            CacheCommands::Clear {
+                force,
+                build,
+                names,
+            } => {
+                commands::cache::run_clear(names, build, force, runtime_override)?;
+                Ok(())
+            }
+        },
+        Commands::Outdated { agents } => {
+            commands::outdated::run(agents, runtime_override)?;
+            Ok(())
+        }
+        Commands::Images { command } => match command {
+            Some(ImagesCommands::Prune { keep, force }) => {
+                commands::images::run_prune(keep, force, runtime_override)?;
+                Ok(())
+            }
+            None => {
+                commands::images::run(runtime_override)?;
+                Ok(())
+            }
+        },
+        Commands::Export {
+            agent,
+            image_tag,
+            output,
+        } => {
+            commands::export::run(&agent, &image_tag, output, runtime_override)?;
+            Ok(())
+        }
+        Commands::Import { file } => {
+            commands::import::run(&file, runtime_override)?;
+            Ok(())
+        }
+        Commands::Session { command } => match command {
+            SessionCommands::Export { name, output } => {
+                commands::session::run_export(&name, output, runtime_override)?;
+                Ok(())
+            }
+            SessionCommands::Import { file, name, paths } => {
+                commands::session::run_import(&file, name, paths, runtime_override)?;
+                Ok(())
+            }
+        },
+        Commands::Doctor { json } => {
+            commands::doctor::run(json, runtime_override)?;
+            Ok(())
+        }
+        Commands::Build { args } => {
+            if args.log {
+                commands::build::run_log(args.agents)?;
+            } else {
+                commands::build::run(build_request(args), false, runtime_override)?;
+            }
+            Ok(())
+        }
+        Commands::Rebuild { args } => {
+            if args.log {
+                commands::build::run_log(args.agents)?;
+            } else {
+                commands::build::run(build_request(args), true, runtime_override)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Convert the parsed `build`/`rebuild` flags for the build command
+fn build_request(args: BuildArgs) -> BuildRequest {
+    BuildRequest {
+        all: args.all,
+        update_tools: args.update_tools,
+        platforms: args.platform,
+        progress: match args.progress {
+            ProgressArg::Auto => Progress::Auto,
+            ProgressArg::Plain => Progress::Plain,
+        },
+        if_older_than: args.if_older_than,
+        agents: args.agents,
     }
 }
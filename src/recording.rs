@@ -0,0 +1,411 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::get_state_home;
+use crate::snapshot::validate_session_name;
+use crate::time::unix_now;
+
+/// Container label marking a session as recorded
+pub const RECORD_LABEL: &str = "klotho.record";
+
+/// Where the host recordings directory is mounted inside the container
+pub const CONTAINER_RECORDINGS_DIR: &str = "/home/agent/.klotho/recordings";
+
+/// A recording of one attach to a session
+#[derive(Debug, Clone)]
+pub struct Recording {
+    /// Recording identifier (unix timestamp the attach started at, then the attaching process)
+    pub id: String,
+    /// Path to the asciicast file (or raw timing file while still recording)
+    pub path: PathBuf,
+    /// Length of the recording in seconds
+    pub duration: f64,
+    /// Size on disk in bytes
+    pub size: u64,
+    /// False while `script` is still writing the raw capture
+    pub finished: bool,
+}
+
+/// Get host directory holding recordings for a session
+pub fn recordings_dir(session_name: &str) -> Result<PathBuf> {
+    validate_session_name(session_name)?;
+    Ok(get_state_home().join("recordings").join(session_name))
+}
+
+/// Generate an identifier for a new recording
+///
+/// The process ID keeps attaches started in the same second from writing to
+/// the same files.
+pub fn new_recording_id() -> String {
+    format!("{}-{}", unix_now(), std::process::id())
+}
+
+/// Wrap an attach command so the terminal stream is captured by `script`
+///
+/// The attach command itself is passed in `KLOTHO_ATTACH_CMD` rather than
+/// inlined, which avoids a second layer of shell quoting. `script` runs its
+/// command through `$SHELL`, so the session wrapper is swapped out for bash
+/// while recording and restored for Zellij.
+pub fn wrap_command(recording_id: &str) -> String {
+    format!(
+        "SESSION_SHELL=\"$SHELL\" SHELL=/bin/bash exec script -q -f -m advanced \
+         --log-out {dir}/{id}.out --log-timing {dir}/{id}.timing \
+         -c 'SHELL=\"$SESSION_SHELL\" exec bash -c \"$KLOTHO_ATTACH_CMD\"'",
+        dir = CONTAINER_RECORDINGS_DIR,
+        id = recording_id
+    )
+}
+
+/// Convert a finished raw capture into an asciicast v2 file
+///
+/// Returns the path of the written `.cast` file, or None if the capture is
+/// missing or `script` hasn't finished writing it yet.
+pub fn finalize(dir: &Path, recording_id: &str, title: &str) -> Result<Option<PathBuf>> {
+    let out_path = dir.join(format!("{}.out", recording_id));
+    let timing_path = dir.join(format!("{}.timing", recording_id));
+
+    if !out_path.exists() || !timing_path.exists() {
+        return Ok(None);
+    }
+
+    let timing = fs::read_to_string(&timing_path).context("failed to read recording timing")?;
+    if !is_capture_finished(&timing) {
+        return Ok(None);
+    }
+
+    let output = fs::read(&out_path).context("failed to read recording output")?;
+    let timestamp = recording_id
+        .split('-')
+        .next()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or_else(unix_now);
+    let cast = convert_typescript(&output, &timing, timestamp, title);
+
+    let cast_path = dir.join(format!("{}.cast", recording_id));
+    fs::write(&cast_path, cast).context("failed to write recording")?;
+
+    fs::remove_file(&out_path).context("failed to remove raw recording output")?;
+    fs::remove_file(&timing_path).context("failed to remove raw recording timing")?;
+
+    Ok(Some(cast_path))
+}
+
+/// Finalize every finished raw capture in a session's recordings directory
+pub fn finalize_pending(session_name: &str) -> Result<()> {
+    let dir = recordings_dir(session_name)?;
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    let title = format!("klotho session {}", session_name);
+    for entry in fs::read_dir(&dir).context("failed to read recordings directory")? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("timing") {
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                finalize(&dir, id, &title)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// List recordings for a session, oldest first
+pub fn list_recordings(session_name: &str) -> Result<Vec<Recording>> {
+    let dir = recordings_dir(session_name)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut recordings = Vec::new();
+    for entry in fs::read_dir(&dir).context("failed to read recordings directory")? {
+        let path = entry?.path();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let id = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+        let size = fs::metadata(&path)?.len();
+
+        match ext {
+            "cast" => {
+                let content = fs::read_to_string(&path)?;
+                let duration = parse_cast(&content)
+                    .map(|(_, events)| events.last().map(|(t, _)| *t).unwrap_or(0.0))
+                    .unwrap_or(0.0);
+                recordings.push(Recording {
+                    id,
+                    path,
+                    duration,
+                    size,
+                    finished: true,
+                });
+            }
+            "timing" => {
+                let timing = fs::read_to_string(&path)?;
+                let duration = parse_timing(&timing).iter().map(|(delay, _)| delay).sum();
+                recordings.push(Recording {
+                    id,
+                    path,
+                    duration,
+                    size,
+                    finished: false,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    recordings.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(recordings)
+}
+
+/// Play an asciicast recording to stdout
+///
+/// Pauses are divided by `speed` and, if set, capped at `idle_limit` seconds.
+pub fn replay(path: &Path, speed: f64, idle_limit: Option<f64>) -> Result<()> {
+    if speed <= 0.0 {
+        anyhow::bail!("speed must be greater than 0");
+    }
+
+    let content = fs::read_to_string(path).context("failed to read recording")?;
+    let (_, events) = parse_cast(&content)?;
+
+    let mut stdout = std::io::stdout().lock();
+    let mut last = 0.0;
+
+    for (time, data) in events {
+        let mut pause = (time - last).max(0.0) / speed;
+        if let Some(limit) = idle_limit {
+            pause = pause.min(limit);
+        }
+        last = time;
+
+        if pause > 0.0 {
+            std::thread::sleep(Duration::from_secs_f64(pause));
+        }
+
+        stdout.write_all(data.as_bytes())?;
+        stdout.flush()?;
+    }
+
+    // Leave the terminal in a sane state whatever the recording ended on
+    stdout.write_all(b"\x1b[0m\x1b[?25h\n")?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Convert a `script` typescript and its timing log to asciicast v2
+pub fn convert_typescript(output: &[u8], timing: &str, timestamp: u64, title: &str) -> String {
+    let (width, height) = parse_terminal_size(timing);
+
+    // Classic captures start with a "Script started on ..." line that isn't in the timing log
+    let mut data = output;
+    if data.starts_with(b"Script started on") {
+        let end = data
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |i| i + 1);
+        data = &data[end..];
+    }
+
+    let header = serde_json::json!({
+        "version": 2,
+        "width": width,
+        "height": height,
+        "timestamp": timestamp,
+        "title": title,
+    });
+
+    let mut cast = header.to_string();
+    cast.push('\n');
+
+    let mut time = 0.0;
+    let mut offset = 0;
+    let mut pending: Vec<u8> = Vec::new();
+
+    for (delay, len) in parse_timing(timing) {
+        time += delay;
+
+        let end = (offset + len).min(data.len());
+        pending.extend_from_slice(&data[offset..end]);
+        offset = end;
+
+        // Chunks can split multi-byte characters; carry incomplete tails forward
+        let text = take_valid_utf8(&mut pending);
+        if text.is_empty() {
+            continue;
+        }
+
+        let event = serde_json::json!([(time * 1_000_000.0).round() / 1_000_000.0, "o", text]);
+        cast.push_str(&event.to_string());
+        cast.push('\n');
+    }
+
+    cast
+}
+
+/// Parse an asciicast v2 file into its header and output events
+pub fn parse_cast(content: &str) -> Result<(serde_json::Value, Vec<(f64, String)>)> {
+    let mut lines = content.lines();
+    let header: serde_json::Value =
+        serde_json::from_str(lines.next().unwrap_or("")).context("invalid recording header")?;
+
+    if header.get("version").and_then(|v| v.as_u64()) != Some(2) {
+        anyhow::bail!("unsupported recording format (expected asciicast v2)");
+    }
+
+    let mut events = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event: serde_json::Value =
+            serde_json::from_str(line).context("invalid recording event")?;
+        let time = event.get(0).and_then(|v| v.as_f64());
+        let kind = event.get(1).and_then(|v| v.as_str());
+        let data = event.get(2).and_then(|v| v.as_str());
+
+        if let (Some(time), Some("o"), Some(data)) = (time, kind, data) {
+            events.push((time, data.to_string()));
+        }
+    }
+
+    Ok((header, events))
+}
+
+/// Parse output entries from a timing log as (delay, byte count) pairs
+///
+/// Handles both the advanced format ("O <delay> <bytes>") and the classic
+/// format ("<delay> <bytes>").
+fn parse_timing(timing: &str) -> Vec<(f64, usize)> {
+    let mut entries = Vec::new();
+
+    for line in timing.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (delay, len) = match fields.as_slice() {
+            ["O", delay, len] => (delay, len),
+            [delay, len] => (delay, len),
+            _ => continue,
+        };
+
+        if let (Ok(delay), Ok(len)) = (delay.parse::<f64>(), len.parse::<usize>()) {
+            entries.push((delay, len));
+        }
+    }
+
+    entries
+}
+
+/// Read terminal size from advanced timing log headers, defaulting to 80x24
+fn parse_terminal_size(timing: &str) -> (u32, u32) {
+    let mut width = 80;
+    let mut height = 24;
+
+    for line in timing.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["H", _, "COLUMNS", value] => width = value.parse().unwrap_or(width),
+            ["H", _, "LINES", value] => height = value.parse().unwrap_or(height),
+            _ => {}
+        }
+    }
+
+    (width, height)
+}
+
+/// Check whether `script` has written its closing entries to a timing log
+fn is_capture_finished(timing: &str) -> bool {
+    timing.lines().any(|line| {
+        let mut fields = line.split_whitespace();
+        fields.next() == Some("H") && matches!(fields.nth(1), Some("DURATION") | Some("EXIT_CODE"))
+    })
+}
+
+/// Drain the longest valid UTF-8 prefix from a buffer
+///
+/// An incomplete trailing sequence stays in the buffer; invalid bytes are
+/// replaced so a corrupt capture can't stall conversion.
+fn take_valid_utf8(buffer: &mut Vec<u8>) -> String {
+    match std::str::from_utf8(buffer) {
+        Ok(text) => {
+            let text = text.to_string();
+            buffer.clear();
+            text
+        }
+        Err(e) if e.error_len().is_none() => {
+            let valid = e.valid_up_to();
+            let text = String::from_utf8_lossy(&buffer[..valid]).into_owned();
+            buffer.drain(..valid);
+            text
+        }
+        Err(_) => {
+            let text = String::from_utf8_lossy(buffer).into_owned();
+            buffer.clear();
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMING: &str = "H 0.000000 START_TIME 2026-01-01 10:00:00 +00:00
+H 0.000000 COLUMNS 120
+H 0.000000 LINES 40
+O 0.500000 6
+O 1.250000 5
+H 0.000000 DURATION 1.750000
+H 0.000000 EXIT_CODE 0
+";
+
+    #[test]
+    fn test_convert_typescript() {
+        let cast = convert_typescript(b"hello world", TIMING, 1767261600, "test");
+        let (header, events) = parse_cast(&cast).unwrap();
+
+        assert_eq!(header["width"], 120);
+        assert_eq!(header["height"], 40);
+        assert_eq!(header["timestamp"], 1767261600);
+        assert_eq!(
+            events,
+            vec![(0.5, "hello ".to_string()), (1.75, "world".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_convert_typescript_classic_header_and_split_utf8() {
+        let mut output = b"Script started on 2026-01-01\n".to_vec();
+        output.extend_from_slice("é!".as_bytes());
+        let cast = convert_typescript(&output, "0.1 1\n0.1 2\n", 0, "test");
+        let (header, events) = parse_cast(&cast).unwrap();
+
+        assert_eq!(header["width"], 80);
+        assert_eq!(events, vec![(0.2, "é!".to_string())]);
+    }
+
+    #[test]
+    fn test_recordings_dir_rejects_unsafe_names() {
+        assert!(recordings_dir("audit").is_ok());
+        assert!(recordings_dir("../audit").is_err());
+        assert!(recordings_dir("a/b").is_err());
+    }
+
+    #[test]
+    fn test_is_capture_finished() {
+        assert!(is_capture_finished(TIMING));
+        assert!(!is_capture_finished("H 0.000000 COLUMNS 120\nO 0.5 6\n"));
+    }
+
+    #[test]
+    fn test_parse_cast_rejects_other_versions() {
+        let result = parse_cast("{\"version\": 1}\n");
+        assert!(result.is_err());
+    }
+}
@@ -1,6 +1,10 @@
 use anyhow::{Context, Result};
 use rust_embed::RustEmbed;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::customization::Customization;
+use crate::tools;
 
 #[derive(RustEmbed)]
 #[folder = "src/resources/"]
@@ -47,26 +51,74 @@ pub fn list_embedded_agents() -> Vec<String> {
     agents
 }
 
-/// Extract embedded resources to a temporary directory for building
-/// Returns the path to the temp directory
-pub fn extract_build_context() -> Result<PathBuf> {
-    let temp_dir = std::env::temp_dir().join("klotho-build");
+/// Build contexts created by this process, numbering their directories
+static CONTEXTS: AtomicUsize = AtomicUsize::new(0);
+
+/// A build context in its own temporary directory, removed when dropped
+///
+/// Each context gets a directory of its own, so concurrent builds (in this
+/// process or another klotho) don't clobber each other.
+#[derive(Debug)]
+pub struct BuildContext {
+    pub path: PathBuf,
+}
 
-    // Clean and recreate
+impl Drop for BuildContext {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Extract build resources to a temporary directory for building
+///
+/// The Containerfile and entrypoint come from the local repo in development
+/// mode, otherwise from the embedded copies; the customization is composed in.
+pub fn extract_build_context(custom: &Customization) -> Result<BuildContext> {
+    let temp_dir = std::env::temp_dir().join(format!(
+        "klotho-build-{}-{}",
+        std::process::id(),
+        CONTEXTS.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    // Clean leftovers of a crashed process with the same PID, then create
     if temp_dir.exists() {
         std::fs::remove_dir_all(&temp_dir)
             .context("Failed to clean temp directory")?;
     }
     std::fs::create_dir_all(&temp_dir)
         .context("Failed to create temp directory")?;
-
-    // Write Containerfile
-    let containerfile = get_containerfile()?;
-    std::fs::write(temp_dir.join("Containerfile"), containerfile)
-        .context("Failed to write Containerfile")?;
+    let context = BuildContext { path: temp_dir };
+    let temp_dir = context.path.clone();
+
+    // Write Containerfile with the customization composed in
+    let containerfile = if should_use_embedded() {
+        get_containerfile()?
+    } else {
+        std::fs::read_to_string("Containerfile").context("Failed to read Containerfile")?
+    };
+    std::fs::write(
+        temp_dir.join("Containerfile"),
+        custom.compose(&containerfile),
+    )
+    .context("Failed to write Containerfile")?;
+    custom.write_files(&temp_dir)?;
+
+    // Write the pinned tool downloads and the script verifying them
+    let manifest = tools::load()?;
+    tools::check_pinned(&manifest)?;
+    std::fs::write(temp_dir.join("tools.lock"), tools::lock(&manifest))
+        .context("Failed to write tools.lock")?;
+    let fetch_tool =
+        Resources::get("fetch-tool.sh").context("fetch-tool.sh not found in embedded resources")?;
+    std::fs::write(temp_dir.join("fetch-tool.sh"), &fetch_tool.data)
+        .context("Failed to write fetch-tool.sh")?;
 
     // Write entrypoint.sh
-    let entrypoint = get_entrypoint()?;
+    let entrypoint = if should_use_embedded() {
+        get_entrypoint()?
+    } else {
+        std::fs::read_to_string("entrypoint.sh").context("Failed to read entrypoint.sh")?
+    };
     std::fs::write(temp_dir.join("entrypoint.sh"), entrypoint)
         .context("Failed to write entrypoint.sh")?;
 
@@ -97,7 +149,7 @@ pub fn extract_build_context() -> Result<PathBuf> {
         }
     }
 
-    Ok(temp_dir)
+    Ok(context)
 }
 
 /// Check if running from repo (has local config/) or needs embedded resources
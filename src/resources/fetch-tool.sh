@@ -0,0 +1,37 @@
+#!/bin/sh
+# Download a tool listed in /etc/klotho/tools.lock and verify its sha256
+# Usage: fetch-tool NAME OUTPUT
+set -eu
+
+name="$1"
+output="$2"
+lock=/etc/klotho/tools.lock
+
+# TARGETARCH is set by the builder; dpkg reports the same under emulation
+arch="${TARGETARCH:-$(dpkg --print-architecture)}"
+
+line="$(awk -v n="$name" -v a="$arch" '$1 == n && ($2 == a || $2 == "any") { print; exit }' "$lock")"
+if [ -z "$line" ]; then
+    echo "fetch-tool: no $name download for $arch in $lock" >&2
+    exit 1
+fi
+
+# Fields: name arch version url sha256 ("-" when not pinned)
+set -- $line
+version="$3"
+url="$4"
+sha256="${5:--}"
+
+if [ "$sha256" = "-" ]; then
+    echo "fetch-tool: no checksum pinned for $name $version ($arch), run: klotho build --update-tools" >&2
+    exit 1
+fi
+
+echo "fetch-tool: $name $version ($arch)" >&2
+curl -fsSL "$url" -o "$output"
+
+if ! echo "$sha256  $output" | sha256sum -c - >/dev/null 2>&1; then
+    echo "fetch-tool: checksum mismatch for $name $version from $url" >&2
+    rm -f "$output"
+    exit 1
+fi
@@ -0,0 +1,362 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::container::{list_container_label, list_containers, ContainerStatus, Runtime};
+use crate::image::AGENT_LABEL;
+
+/// A session container known to the runtime
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    /// Session name as given to `klotho start -n`
+    pub name: String,
+    pub agent: String,
+    pub container: String,
+    pub status: ContainerStatus,
+}
+
+impl Session {
+    pub fn new(container: &str, status: ContainerStatus) -> Self {
+        Self::with_agent(container, None, status)
+    }
+
+    /// Build a session whose agent is known from the container's agent label
+    ///
+    /// Without the label the agent is guessed from the container name.
+    pub fn with_agent(container: &str, agent: Option<&str>, status: ContainerStatus) -> Self {
+        let (agent, name) = match agent {
+            Some(agent) => (agent.to_string(), session_name(container, agent)),
+            None => split_container_name(container),
+        };
+        Session {
+            name,
+            agent,
+            container: container.to_string(),
+            status,
+        }
+    }
+
+    /// One-line description for confirmation prompts, e.g. "exp-1 (claude)"
+    pub fn describe(&self) -> String {
+        format!("{} ({})", self.name, self.agent)
+    }
+}
+
+/// Which sessions a bulk command applies to
+#[derive(Debug, Clone, Default)]
+pub struct Selector {
+    /// Session names or glob patterns (`*` and `?`)
+    pub patterns: Vec<String>,
+    /// Select every session
+    pub all: bool,
+    pub agent: Option<String>,
+    pub status: Option<ContainerStatus>,
+}
+
+impl Selector {
+    /// Whether the selector names exactly one session without globs or filters
+    pub fn is_single(&self) -> bool {
+        !self.all
+            && self.agent.is_none()
+            && self.status.is_none()
+            && self.patterns.len() <= 1
+            && !self.patterns.iter().any(|p| is_glob(p))
+    }
+
+    /// Check a session against the patterns and filters
+    pub fn matches(&self, session: &Session) -> bool {
+        if let Some(agent) = &self.agent {
+            if &session.agent != agent {
+                return false;
+            }
+        }
+        if let Some(status) = &self.status {
+            if &session.status != status {
+                return false;
+            }
+        }
+
+        // Filters alone select from every session
+        self.patterns.is_empty() || self.patterns.iter().any(|p| glob_match(p, &session.name))
+    }
+}
+
+/// List all klotho session containers
+pub fn list_sessions(runtime: Runtime) -> Result<Vec<Session>> {
+    let agents = list_container_label(runtime, AGENT_LABEL)?;
+    let mut sessions: Vec<Session> = list_containers(runtime)?
+        .into_iter()
+        .map(|(container, status)| {
+            let agent = agents.get(&container).map(String::as_str);
+            Session::with_agent(&container, agent, status)
+        })
+        .collect();
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(sessions)
+}
+
+/// Session names of the given containers, for listing who uses a volume
+pub fn session_names(sessions: &[Session], containers: &[String]) -> Vec<String> {
+    containers
+        .iter()
+        .map(
+            |container| match sessions.iter().find(|s| &s.container == container) {
+                Some(session) => session.name.clone(),
+                None => split_container_name(container).1,
+            },
+        )
+        .collect()
+}
+
+/// Resolve a selector to the sessions it matches
+///
+/// With no patterns, flags or filters the "default" session is selected, as
+/// for single-session commands. A plain name that matches nothing is an error.
+pub fn select(runtime: Runtime, selector: &Selector) -> Result<Vec<Session>> {
+    let mut selector = selector.clone();
+    if !selector.all
+        && selector.agent.is_none()
+        && selector.status.is_none()
+        && selector.patterns.is_empty()
+    {
+        selector.patterns.push("default".to_string());
+    }
+
+    let sessions = list_sessions(runtime)?;
+    for pattern in selector.patterns.iter().filter(|p| !is_glob(p)) {
+        if !sessions.iter().any(|s| &s.name == pattern) {
+            bail!("Session '{}' not found", pattern);
+        }
+    }
+
+    Ok(sessions
+        .into_iter()
+        .filter(|s| selector.matches(s))
+        .collect())
+}
+
+/// List the sessions an action applies to and ask for confirmation
+///
+/// `items` describes one session per entry, see [`Session::describe`].
+pub fn confirm(action: &str, items: &[String]) -> Result<bool> {
+    confirm_items(action, "session", items)
+}
+
+/// List the things of one kind (images, volumes, ...) an action applies to and
+/// ask for confirmation
+pub fn confirm_items(action: &str, kind: &str, items: &[String]) -> Result<bool> {
+    println!(
+        "This will {} {} {}{}:",
+        action,
+        items.len(),
+        kind,
+        if items.len() == 1 { "" } else { "s" }
+    );
+    for item in items {
+        println!("  {}", item);
+    }
+
+    ask("Continue?")
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no
+pub fn ask(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let answer = input.trim().to_lowercase();
+    Ok(answer == "y" || answer == "yes")
+}
+
+/// Describe each session for a confirmation prompt
+pub fn describe(sessions: &[Session]) -> Vec<String> {
+    sessions.iter().map(Session::describe).collect()
+}
+
+/// Get the session name from a container name whose agent is known
+///
+/// Container names are klotho-session-<agent>-<name>, or <agent>-<name> for
+/// legacy containers.
+fn session_name(container: &str, agent: &str) -> String {
+    let rest = container
+        .strip_prefix("klotho-session-")
+        .unwrap_or(container);
+
+    match rest.strip_prefix(agent).and_then(|r| r.strip_prefix('-')) {
+        Some(name) => name.to_string(),
+        None => split_container_name(container).1,
+    }
+}
+
+/// Get the agent of a session's container from its labels
+///
+/// Containers created without the agent label fall back to their name,
+/// klotho-session-<agent>-<name> or <agent>-<name> (legacy).
+pub fn session_agent(
+    labels: &HashMap<String, String>,
+    container: &str,
+    session_name: &str,
+) -> Result<String> {
+    if let Some(agent) = labels.get(AGENT_LABEL) {
+        return Ok(agent.clone());
+    }
+
+    let rest = container
+        .strip_prefix("klotho-session-")
+        .unwrap_or(container);
+    match rest.strip_suffix(&format!("-{}", session_name)) {
+        Some(agent) if !agent.is_empty() => Ok(agent.to_string()),
+        _ => bail!(
+            "Cannot extract agent type from container name: {}",
+            container
+        ),
+    }
+}
+
+/// Split a container name into (agent, session name)
+///
+/// Only a fallback for containers created without the agent label: the agent
+/// is taken to end at the first hyphen, which is wrong for hyphenated agents.
+pub fn split_container_name(container: &str) -> (String, String) {
+    let rest = container
+        .strip_prefix("klotho-session-")
+        .unwrap_or(container);
+
+    match rest.split_once('-') {
+        Some((agent, name)) => (agent.to_string(), name.to_string()),
+        None => ("unknown".to_string(), rest.to_string()),
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Match a name against a glob pattern supporting `*` and `?`
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Iterative matching with backtracking to the last `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("exp-*", "exp-1"));
+        assert!(glob_match("exp-*", "exp-"));
+        assert!(!glob_match("exp-*", "main"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(glob_match("*-claude-*", "fanout-1-claude-2"));
+        assert!(glob_match("default", "default"));
+        assert!(!glob_match("default", "default2"));
+    }
+
+    #[test]
+    fn test_split_container_name() {
+        assert_eq!(
+            split_container_name("klotho-session-claude-default"),
+            ("claude".to_string(), "default".to_string())
+        );
+        assert_eq!(
+            split_container_name("klotho-session-opencode-fanout-1-opencode-2"),
+            ("opencode".to_string(), "fanout-1-opencode-2".to_string())
+        );
+        assert_eq!(
+            split_container_name("claude-frontend"),
+            ("claude".to_string(), "frontend".to_string())
+        );
+    }
+
+    #[test]
+    fn test_session_agent() {
+        let mut labels = HashMap::new();
+        assert_eq!(
+            session_agent(&labels, "klotho-session-my-agent-dev", "dev").unwrap(),
+            "my-agent"
+        );
+        assert_eq!(
+            session_agent(&labels, "claude-dev", "dev").unwrap(),
+            "claude"
+        );
+        assert!(session_agent(&labels, "klotho-session-claude-dev", "other").is_err());
+
+        labels.insert(AGENT_LABEL.to_string(), "opencode".to_string());
+        assert_eq!(
+            session_agent(&labels, "klotho-session-claude-dev", "dev").unwrap(),
+            "opencode"
+        );
+    }
+
+    #[test]
+    fn test_session_with_agent_label() {
+        let session = Session::with_agent(
+            "klotho-session-my-agent-exp-1",
+            Some("my-agent"),
+            ContainerStatus::Stopped,
+        );
+        assert_eq!(session.agent, "my-agent");
+        assert_eq!(session.name, "exp-1");
+
+        let legacy =
+            Session::with_agent("my-agent-main", Some("my-agent"), ContainerStatus::Stopped);
+        assert_eq!(legacy.name, "main");
+    }
+
+    #[test]
+    fn test_selector_matches() {
+        let running = Session::new("klotho-session-claude-exp-1", ContainerStatus::Running);
+        let stopped = Session::new("klotho-session-opencode-exp-2", ContainerStatus::Stopped);
+
+        let selector = Selector {
+            patterns: vec!["exp-*".to_string()],
+            ..Default::default()
+        };
+        assert!(selector.matches(&running) && selector.matches(&stopped));
+        assert!(!selector.is_single());
+
+        let selector = Selector {
+            status: Some(ContainerStatus::Stopped),
+            ..Default::default()
+        };
+        assert!(!selector.matches(&running) && selector.matches(&stopped));
+
+        let selector = Selector {
+            agent: Some("claude".to_string()),
+            ..Default::default()
+        };
+        assert!(selector.matches(&running) && !selector.matches(&stopped));
+
+        let selector = Selector {
+            patterns: vec!["exp-1".to_string()],
+            ..Default::default()
+        };
+        assert!(selector.is_single());
+    }
+}
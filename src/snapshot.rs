@@ -0,0 +1,554 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::get_state_home;
+use crate::git;
+use crate::time::unix_now;
+
+/// Number of automatic snapshots kept per session
+const AUTO_SNAPSHOT_KEEP: usize = 10;
+
+/// Label prefix for snapshots taken by KLOTHO_AUTO_SNAPSHOT
+pub const AUTO_SNAPSHOT_PREFIX: &str = "auto-";
+
+/// Snapshot of every project directory mounted into a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub label: String,
+    pub session: String,
+    /// Unix timestamp the snapshot was taken at
+    pub created: u64,
+    pub dirs: Vec<DirSnapshot>,
+}
+
+/// Snapshot of a single project directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DirSnapshot {
+    /// Git work tree captured as a commit under refs/klotho/snapshots/
+    Git {
+        path: String,
+        /// HEAD at snapshot time (None for a repo without commits)
+        head: Option<String>,
+        /// Checked-out branch at snapshot time (None when detached)
+        branch: Option<String>,
+        /// Commit holding the full work tree, including untracked files
+        commit: String,
+    },
+    /// Plain directory captured as content-addressed file copies
+    Copy { path: String, files: Vec<FileEntry> },
+}
+
+/// A file in a copy snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Path relative to the snapshotted directory
+    pub path: String,
+    /// sha256 of the content (or of the link target for symlinks)
+    pub hash: String,
+    pub mode: u32,
+    #[serde(default)]
+    pub symlink: bool,
+}
+
+impl DirSnapshot {
+    pub fn path(&self) -> &str {
+        match self {
+            DirSnapshot::Git { path, .. } | DirSnapshot::Copy { path, .. } => path,
+        }
+    }
+}
+
+/// Get directory holding snapshot manifests for a session
+fn snapshots_dir(session_name: &str) -> Result<PathBuf> {
+    validate_session_name(session_name)?;
+    Ok(get_state_home().join("snapshots").join(session_name))
+}
+
+/// Get the content-addressed object store shared by all copy snapshots
+///
+/// It lives outside `snapshots/` so no session name can share its directory.
+fn objects_dir() -> PathBuf {
+    get_state_home().join("snapshot-objects")
+}
+
+/// Whether a name is safe as a file name and git ref component
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !name.contains("..")
+        && !name.ends_with(".lock")
+}
+
+/// Validate a snapshot label (also used as a git ref component and file name)
+pub fn validate_label(label: &str) -> Result<()> {
+    if !is_valid_name(label) {
+        anyhow::bail!(
+            "invalid snapshot label '{}' - use letters, digits, '-', '_' and '.'",
+            label
+        );
+    }
+    Ok(())
+}
+
+/// Validate a session name before it becomes part of a state path or git ref
+pub(crate) fn validate_session_name(session_name: &str) -> Result<()> {
+    if !is_valid_name(session_name) {
+        anyhow::bail!("invalid session name '{}'", session_name);
+    }
+    Ok(())
+}
+
+/// Generate a label from a prefix and the current time
+pub fn timestamped_label(prefix: &str) -> String {
+    format!("{}{}", prefix, unix_now())
+}
+
+/// Snapshot project directories under a label
+pub fn create(session_name: &str, label: &str, dirs: &[PathBuf]) -> Result<Snapshot> {
+    validate_label(label)?;
+
+    let manifest_path = snapshots_dir(session_name)?.join(format!("{}.toml", label));
+    if manifest_path.exists() {
+        anyhow::bail!(
+            "snapshot '{}' already exists for session '{}'",
+            label,
+            session_name
+        );
+    }
+
+    let mut snapshot = Snapshot {
+        label: label.to_string(),
+        session: session_name.to_string(),
+        created: unix_now(),
+        dirs: Vec::new(),
+    };
+
+    for dir in dirs {
+        let dir_snapshot = if git::is_toplevel(dir) {
+            snapshot_git(dir, session_name, label)?
+        } else {
+            snapshot_copy(dir)?
+        };
+        snapshot.dirs.push(dir_snapshot);
+    }
+
+    save(&snapshot)?;
+    Ok(snapshot)
+}
+
+/// Load a snapshot manifest
+pub fn load(session_name: &str, label: &str) -> Result<Snapshot> {
+    validate_label(label)?;
+    let path = snapshots_dir(session_name)?.join(format!("{}.toml", label));
+    let content = fs::read_to_string(&path).with_context(|| {
+        format!(
+            "snapshot '{}' not found for session '{}'",
+            label, session_name
+        )
+    })?;
+    toml::from_str(&content).context("failed to parse snapshot manifest")
+}
+
+/// List snapshots for a session, oldest first
+pub fn list(session_name: &str) -> Result<Vec<Snapshot>> {
+    let dir = snapshots_dir(session_name)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&dir).context("failed to read snapshots directory")? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("toml") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        match toml::from_str::<Snapshot>(&content) {
+            Ok(snapshot) => snapshots.push(snapshot),
+            Err(e) => eprintln!(
+                "warning: skipping invalid snapshot {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    snapshots.sort_by(|a, b| a.created.cmp(&b.created).then(a.label.cmp(&b.label)));
+    Ok(snapshots)
+}
+
+/// Restore every directory in a snapshot
+pub fn restore(snapshot: &Snapshot) -> Result<()> {
+    for dir in &snapshot.dirs {
+        match dir {
+            DirSnapshot::Git {
+                path,
+                head,
+                branch,
+                commit,
+            } => restore_git(Path::new(path), head.as_deref(), branch.as_deref(), commit)?,
+            DirSnapshot::Copy { path, files } => restore_copy(Path::new(path), files)?,
+        }
+    }
+    Ok(())
+}
+
+/// Delete a snapshot manifest and its git refs
+///
+/// Copy objects are left in the shared store since other snapshots may use them.
+pub fn delete(snapshot: &Snapshot) -> Result<()> {
+    for dir in &snapshot.dirs {
+        if let DirSnapshot::Git { path, .. } = dir {
+            let ref_name = snapshot_ref(&snapshot.session, &snapshot.label);
+            // The repo may have moved or been deleted since; the manifest still goes
+            let _ = git(Path::new(path), &["update-ref", "-d", &ref_name]);
+        }
+    }
+
+    let path = snapshots_dir(&snapshot.session)?.join(format!("{}.toml", snapshot.label));
+    fs::remove_file(&path).context("failed to remove snapshot manifest")?;
+    Ok(())
+}
+
+/// Take an automatic snapshot and drop the oldest ones beyond the retention limit
+pub fn auto_snapshot(session_name: &str, dirs: &[PathBuf]) -> Result<Snapshot> {
+    let snapshot = create(session_name, &timestamped_label(AUTO_SNAPSHOT_PREFIX), dirs)?;
+
+    let autos: Vec<Snapshot> = list(session_name)?
+        .into_iter()
+        .filter(|s| s.label.starts_with(AUTO_SNAPSHOT_PREFIX))
+        .collect();
+
+    if autos.len() > AUTO_SNAPSHOT_KEEP {
+        for old in &autos[..autos.len() - AUTO_SNAPSHOT_KEEP] {
+            delete(old)?;
+        }
+    }
+
+    Ok(snapshot)
+}
+
+/// Check whether automatic snapshots are enabled (KLOTHO_AUTO_SNAPSHOT)
+pub fn auto_snapshot_enabled() -> bool {
+    std::env::var("KLOTHO_AUTO_SNAPSHOT")
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+fn save(snapshot: &Snapshot) -> Result<()> {
+    let dir = snapshots_dir(&snapshot.session)?;
+    fs::create_dir_all(&dir).context("failed to create snapshots directory")?;
+
+    let content = toml::to_string(snapshot).context("failed to serialize snapshot")?;
+    fs::write(dir.join(format!("{}.toml", snapshot.label)), content)
+        .context("failed to write snapshot manifest")?;
+    Ok(())
+}
+
+fn snapshot_ref(session_name: &str, label: &str) -> String {
+    format!("refs/klotho/snapshots/{}/{}", session_name, label)
+}
+
+// ===== Git snapshots =====
+
+/// Capture the work tree (tracked and untracked, minus ignored) as a commit
+fn snapshot_git(dir: &Path, session_name: &str, label: &str) -> Result<DirSnapshot> {
+    let head = git(dir, &["rev-parse", "--verify", "-q", "HEAD"]).ok();
+    let branch = git(dir, &["symbolic-ref", "-q", "--short", "HEAD"]).ok();
+
+    let tree = git::write_worktree_tree(dir)
+        .with_context(|| format!("failed to snapshot {}", dir.display()))?;
+
+    let message = format!("klotho snapshot {}", label);
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", message.as_str()];
+    if let Some(head) = &head {
+        args.extend(["-p", head.as_str()]);
+    }
+    let commit = git(dir, &args)?;
+
+    git(
+        dir,
+        &["update-ref", &snapshot_ref(session_name, label), &commit],
+    )?;
+
+    Ok(DirSnapshot::Git {
+        path: dir.display().to_string(),
+        head,
+        branch,
+        commit,
+    })
+}
+
+/// Restore a git work tree to a snapshot commit
+fn restore_git(dir: &Path, head: Option<&str>, branch: Option<&str>, commit: &str) -> Result<()> {
+    // Put HEAD (and branch) back where they were
+    match (branch, head) {
+        (Some(branch), Some(head)) => {
+            git(dir, &["checkout", "-q", "-f", branch])?;
+            git(dir, &["reset", "-q", "--hard", head])?;
+        }
+        (None, Some(head)) => {
+            git(dir, &["checkout", "-q", "-f", "--detach", head])?;
+        }
+        _ => {}
+    }
+
+    // Drop files created since, then write the snapshot's tree over the top
+    git(dir, &["clean", "-q", "-f", "-d"])?;
+    git(dir, &["read-tree", commit])?;
+    git(dir, &["checkout-index", "-a", "-f"])?;
+
+    // Remove tracked files that had been deleted when the snapshot was taken
+    if let Some(head) = head {
+        let deleted = git(
+            dir,
+            &["diff", "--name-only", "--diff-filter=D", head, commit],
+        )?;
+        for path in deleted.lines().filter(|l| !l.is_empty()) {
+            let _ = fs::remove_file(dir.join(path));
+        }
+        // Leave snapshot changes unstaged, as they were
+        git(dir, &["read-tree", head])?;
+    } else {
+        git(dir, &["read-tree", "--empty"])?;
+    }
+
+    Ok(())
+}
+
+/// Run git in a directory, returning trimmed stdout
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    git::run(snapshot_git_command(dir), args)
+}
+
+fn snapshot_git_command(dir: &Path) -> Command {
+    let mut cmd = git::command(dir);
+
+    // Snapshot commits must not depend on the user's git identity
+    cmd.env("GIT_AUTHOR_NAME", "klotho")
+        .env("GIT_AUTHOR_EMAIL", "klotho@localhost")
+        .env("GIT_COMMITTER_NAME", "klotho")
+        .env("GIT_COMMITTER_EMAIL", "klotho@localhost");
+    cmd
+}
+
+// ===== Copy snapshots =====
+
+/// Copy every file into the object store and record a manifest
+fn snapshot_copy(dir: &Path) -> Result<DirSnapshot> {
+    let objects = objects_dir();
+    fs::create_dir_all(&objects).context("failed to create snapshot object store")?;
+
+    let mut files = Vec::new();
+    for rel in walk_files(dir)? {
+        let full = dir.join(&rel);
+        let meta = fs::symlink_metadata(&full)?;
+        let symlink = meta.file_type().is_symlink();
+
+        let content = if symlink {
+            fs::read_link(&full)?
+                .to_string_lossy()
+                .into_owned()
+                .into_bytes()
+        } else {
+            fs::read(&full).with_context(|| format!("failed to read {}", full.display()))?
+        };
+
+        let hash = hash_bytes(&content);
+        let object = object_path(&objects, &hash);
+        if !object.exists() {
+            fs::create_dir_all(object.parent().unwrap())?;
+            fs::write(&object, &content).context("failed to write snapshot object")?;
+        }
+
+        files.push(FileEntry {
+            path: rel.to_string_lossy().to_string(),
+            hash,
+            mode: file_mode(&meta),
+            symlink,
+        });
+    }
+
+    Ok(DirSnapshot::Copy {
+        path: dir.display().to_string(),
+        files,
+    })
+}
+
+/// Restore a plain directory from a copy manifest
+fn restore_copy(dir: &Path, files: &[FileEntry]) -> Result<()> {
+    let objects = objects_dir();
+    let wanted: HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+
+    // Remove files that didn't exist at snapshot time
+    for rel in walk_files(dir)? {
+        if !wanted.contains(rel.to_string_lossy().as_ref()) {
+            fs::remove_file(dir.join(&rel))?;
+        }
+    }
+
+    for entry in files {
+        let target = dir.join(&entry.path);
+        let content = fs::read(object_path(&objects, &entry.hash))
+            .with_context(|| format!("snapshot object missing for {}", entry.path))?;
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Skip files that are already identical
+        if let Ok(meta) = fs::symlink_metadata(&target) {
+            let current = if meta.file_type().is_symlink() {
+                fs::read_link(&target)
+                    .ok()
+                    .map(|t| t.to_string_lossy().into_owned().into_bytes())
+            } else {
+                fs::read(&target).ok()
+            };
+            if meta.file_type().is_symlink() == entry.symlink
+                && current.map(|c| hash_bytes(&c)) == Some(entry.hash.clone())
+            {
+                continue;
+            }
+            if meta.is_dir() {
+                fs::remove_dir_all(&target)?;
+            } else {
+                fs::remove_file(&target)?;
+            }
+        }
+
+        if entry.symlink {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(String::from_utf8_lossy(&content).as_ref(), &target)?;
+        } else {
+            fs::write(&target, &content)?;
+            set_file_mode(&target, entry.mode)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively list files and symlinks under a directory (relative paths, sorted)
+fn walk_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut stack = vec![PathBuf::new()];
+
+    while let Some(rel) = stack.pop() {
+        let dir = root.join(&rel);
+        for entry in
+            fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let entry = entry?;
+            let child = rel.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                stack.push(child);
+            } else {
+                files.push(child);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn object_path(objects: &Path, hash: &str) -> PathBuf {
+    objects.join(&hash[..2]).join(&hash[2..])
+}
+
+fn hash_bytes(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[cfg(unix)]
+fn file_mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn file_mode(_meta: &fs::Metadata) -> u32 {
+    0o644
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_label() {
+        assert!(validate_label("before-refactor").is_ok());
+        assert!(validate_label("v1.2_ok").is_ok());
+        assert!(validate_label("").is_err());
+        assert!(validate_label("a/b").is_err());
+        assert!(validate_label("a..b").is_err());
+        assert!(validate_label("-flag").is_err());
+        assert!(validate_label("x.lock").is_err());
+    }
+
+    #[test]
+    fn test_paths_reject_traversal() {
+        assert!(snapshots_dir("default").is_ok());
+        assert!(snapshots_dir("../../etc").is_err());
+        assert!(snapshots_dir("..").is_err());
+        assert!(snapshots_dir("a/b").is_err());
+        assert!(load("default", "../escape").is_err());
+    }
+
+    #[test]
+    fn test_manifest_roundtrip() {
+        let snapshot = Snapshot {
+            label: "one".to_string(),
+            session: "default".to_string(),
+            created: 1,
+            dirs: vec![
+                DirSnapshot::Git {
+                    path: "/p/a".to_string(),
+                    head: Some("abc".to_string()),
+                    branch: None,
+                    commit: "def".to_string(),
+                },
+                DirSnapshot::Copy {
+                    path: "/p/b".to_string(),
+                    files: vec![FileEntry {
+                        path: "x.txt".to_string(),
+                        hash: "00ff".to_string(),
+                        mode: 0o644,
+                        symlink: false,
+                    }],
+                },
+            ],
+        };
+
+        let parsed: Snapshot = toml::from_str(&toml::to_string(&snapshot).unwrap()).unwrap();
+        assert_eq!(parsed.dirs.len(), 2);
+        assert_eq!(parsed.dirs[0].path(), "/p/a");
+        assert!(
+            matches!(&parsed.dirs[1], DirSnapshot::Copy { files, .. } if files[0].mode == 0o644)
+        );
+    }
+}
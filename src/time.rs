@@ -0,0 +1,197 @@
+use anyhow::{bail, Context, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Current time as a Unix timestamp
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Parse a duration such as "30m", "12h", "7d" or "2w"
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("invalid duration '{}' (expected e.g. 12h, 7d, 2w)", s))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        _ => bail!("invalid duration '{}' (expected e.g. 12h, 7d, 2w)", s),
+    };
+
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Format an age in seconds as a short human-readable string (e.g. "3d")
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86_400),
+    }
+}
+
+/// Format a Unix timestamp as a compact UTC string usable in image tags ("20240115-103000")
+pub fn format_compact(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+/// Parse a timestamp as printed by podman or docker into a Unix timestamp
+///
+/// Accepts RFC 3339 ("2024-01-15T10:30:00.123Z") and Go's default time format
+/// ("2024-01-15 10:30:00.123 +0100 CET"). Zero times (year 1) yield `None`.
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if s.len() < 19 || !s.is_char_boundary(19) {
+        return None;
+    }
+
+    let (datetime, rest) = s.split_at(19);
+    let year: i64 = datetime.get(0..4)?.parse().ok()?;
+    let month: u32 = datetime.get(5..7)?.parse().ok()?;
+    let day: u32 = datetime.get(8..10)?.parse().ok()?;
+    let hour: i64 = datetime.get(11..13)?.parse().ok()?;
+    let minute: i64 = datetime.get(14..16)?.parse().ok()?;
+    let second: i64 = datetime.get(17..19)?.parse().ok()?;
+
+    // Skip fractional seconds, then read the UTC offset
+    let rest = rest
+        .trim_start_matches(|c: char| c == '.' || c.is_ascii_digit())
+        .trim_start();
+    let offset = parse_offset(rest)?;
+
+    let days = days_from_civil(year, month, day);
+    let timestamp = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+    u64::try_from(timestamp).ok()
+}
+
+/// Parse a UTC offset ("Z", "+0100", "-05:00") into seconds east of UTC
+fn parse_offset(s: &str) -> Option<i64> {
+    if s.is_empty() || s.starts_with('Z') {
+        return Some(0);
+    }
+
+    let sign = match s.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits: String = s[1..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == ':')
+        .filter(|c| *c != ':')
+        .collect();
+    if digits.len() != 4 {
+        return None;
+    }
+
+    let hours: i64 = digits[0..2].parse().ok()?;
+    let minutes: i64 = digits[2..4].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Proleptic Gregorian date for a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("12h").unwrap(), Duration::from_secs(43_200));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            Duration::from_secs(1_209_600)
+        );
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        // docker (RFC 3339)
+        assert_eq!(
+            parse_timestamp("2024-01-15T10:30:00.123456789Z"),
+            Some(1_705_314_600)
+        );
+        // podman (Go default format)
+        assert_eq!(
+            parse_timestamp("2024-01-15 11:30:00.123456789 +0100 CET"),
+            Some(1_705_314_600)
+        );
+        assert_eq!(
+            parse_timestamp("2024-01-15T05:30:00-05:00"),
+            Some(1_705_314_600)
+        );
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        // Containers that never ran report a zero time
+        assert_eq!(parse_timestamp("0001-01-01 00:00:00 +0000 UTC"), None);
+        assert_eq!(parse_timestamp("garbage"), None);
+    }
+
+    #[test]
+    fn test_format_compact() {
+        assert_eq!(format_compact(0), "19700101-000000");
+        assert_eq!(format_compact(1_705_314_600), "20240115-103000");
+        // Leap day round trip
+        let leap = parse_timestamp("2024-02-29T23:59:59Z").unwrap();
+        assert_eq!(format_compact(leap), "20240229-235959");
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(5), "5s");
+        assert_eq!(format_age(120), "2m");
+        assert_eq!(format_age(7200), "2h");
+        assert_eq!(format_age(3 * 86_400 + 5), "3d");
+    }
+}
@@ -0,0 +1,299 @@
+use anyhow::{bail, Context, Result};
+use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::config::get_config_home;
+use crate::resources::Resources;
+use crate::time;
+
+/// Checksum and lock key for tools with a single download
+const ANY_ARCH: &str = "any";
+
+/// A download used by image builds, pinned to a version and checksums
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tool {
+    pub version: String,
+    /// Where --update-tools finds new versions: "github:OWNER/REPO" or "url"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Download URL, may contain {version} and {arch}
+    pub url: String,
+    /// Image architecture (amd64, arm64) to the name used in the URL
+    #[serde(default)]
+    pub arch: BTreeMap<String, String>,
+    /// Image architecture (or "any") to the download's sha256
+    #[serde(default)]
+    pub sha256: BTreeMap<String, String>,
+}
+
+impl Tool {
+    /// Download URL per image architecture ("any" for single downloads)
+    pub fn downloads(&self) -> Vec<(String, String)> {
+        let url = self.url.replace("{version}", &self.version);
+        if self.arch.is_empty() {
+            return vec![(ANY_ARCH.to_string(), url)];
+        }
+        self.arch
+            .iter()
+            .map(|(arch, name)| (arch.clone(), url.replace("{arch}", name)))
+            .collect()
+    }
+}
+
+/// Tools by name
+pub type Manifest = BTreeMap<String, Tool>;
+
+/// User manifest written by --update-tools, overriding the embedded one
+fn user_manifest_path() -> PathBuf {
+    let (config_home, _) = get_config_home();
+    config_home.join("tools.toml")
+}
+
+fn embedded_manifest() -> Result<String> {
+    let file =
+        Resources::get("tools.toml").context("tools.toml not found in embedded resources")?;
+    Ok(String::from_utf8_lossy(&file.data).into_owned())
+}
+
+/// Load the tool manifest: the user's refreshed copy if any, else the embedded one
+pub fn load() -> Result<Manifest> {
+    let path = user_manifest_path();
+    if path.exists() {
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        return parse(&content).with_context(|| format!("failed to parse {}", path.display()));
+    }
+    parse(&embedded_manifest()?).context("failed to parse embedded tools.toml")
+}
+
+pub fn parse(content: &str) -> Result<Manifest> {
+    Ok(toml::from_str(content)?)
+}
+
+/// Fail unless every download has a checksum, so builds never run anything unverified
+pub fn check_pinned(manifest: &Manifest) -> Result<()> {
+    let mut missing = Vec::new();
+    for (name, tool) in manifest {
+        for (arch, _) in tool.downloads() {
+            if tool
+                .sha256
+                .get(&arch)
+                .is_none_or(|sha256| sha256.is_empty())
+            {
+                missing.push(format!("{} ({})", name, arch));
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        bail!(
+            "the tool manifest has no sha256 for {}
+pin them with: klotho build --update-tools",
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Render the lock file read by fetch-tool inside the build
+///
+/// One line per download: `name arch version url sha256`, "-" for a missing checksum.
+pub fn lock(manifest: &Manifest) -> String {
+    let mut lines = String::new();
+    for (name, tool) in manifest {
+        for (arch, url) in tool.downloads() {
+            let sha256 = tool.sha256.get(&arch).map(String::as_str).unwrap_or("-");
+            lines.push_str(&format!(
+                "{} {} {} {} {}\n",
+                name, arch, tool.version, url, sha256
+            ));
+        }
+    }
+    lines
+}
+
+/// Refresh versions and checksums of every tool and save the user manifest
+pub fn update() -> Result<()> {
+    let mut manifest = load()?;
+
+    for (name, tool) in manifest.iter_mut() {
+        let previous = tool.clone();
+        match tool.source.as_deref() {
+            Some(source) if source.starts_with("github:") => {
+                tool.version = latest_release(&source["github:".len()..])
+                    .with_context(|| format!("failed to find the latest {} release", name))?;
+            }
+            Some("url") | None => {}
+            Some(other) => bail!("unknown source '{}' for tool {}", other, name),
+        }
+
+        tool.sha256.clear();
+        for (arch, url) in tool.downloads() {
+            let sha256 = sha256_of_url(&url)
+                .with_context(|| format!("failed to download {} ({})", name, arch))?;
+            tool.sha256.insert(arch, sha256);
+        }
+
+        // Unversioned downloads get a date once their content changes
+        if tool.source.as_deref() == Some("url") && tool.sha256 != previous.sha256 {
+            tool.version = time::format_compact(time::unix_now())[..8].to_string();
+        }
+
+        if *tool == previous {
+            eprintln!("  {} {} {}", "✓".green(), name, tool.version.dimmed());
+        } else {
+            eprintln!(
+                "  {} {} {} → {}",
+                "↑".cyan(),
+                name,
+                previous.version.dimmed(),
+                tool.version
+            );
+        }
+    }
+
+    let path = user_manifest_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let content = format!(
+        "{}\n{}",
+        header(&embedded_manifest()?),
+        toml::to_string(&manifest)?
+    );
+    std::fs::write(&path, content)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+
+    eprintln!("{} Updated tool manifest {}", "✓".green(), path.display());
+    Ok(())
+}
+
+/// Leading comment block of a manifest
+fn header(content: &str) -> String {
+    content
+        .lines()
+        .take_while(|line| line.starts_with('#') || line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_string()
+        + "\n"
+}
+
+/// Latest release version of a GitHub repository, without a leading "v"
+fn latest_release(repo: &str) -> Result<String> {
+    let url = format!("https://api.github.com/repos/{}/releases/latest", repo);
+    let body = curl(&url)?;
+    let release: serde_json::Value =
+        serde_json::from_slice(&body).context("invalid release response")?;
+    let tag = release
+        .get("tag_name")
+        .and_then(|tag| tag.as_str())
+        .context("release has no tag_name")?;
+    Ok(tag.trim_start_matches('v').to_string())
+}
+
+fn sha256_of_url(url: &str) -> Result<String> {
+    Ok(format!("{:x}", Sha256::digest(curl(url)?)))
+}
+
+fn curl(url: &str) -> Result<Vec<u8>> {
+    let output = Command::new("curl")
+        .args(["-fsSL", url])
+        .output()
+        .context("failed to run curl")?;
+    if !output.status.success() {
+        bail!(
+            "{}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_manifest_parses() {
+        let manifest = parse(&embedded_manifest().unwrap()).unwrap();
+        for name in [
+            "zellij",
+            "starship",
+            "uv",
+            "claude-installer",
+            "opencode-installer",
+        ] {
+            assert!(manifest.contains_key(name), "missing {}", name);
+        }
+    }
+
+    #[test]
+    #[ignore = "src/resources/tools.toml needs its sha256 tables filled in from klotho build --update-tools"]
+    fn test_embedded_manifest_pinned() {
+        check_pinned(&parse(&embedded_manifest().unwrap()).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_lock() {
+        let manifest = parse(
+            r#"
+[zellij]
+version = "0.42.2"
+source = "github:zellij-org/zellij"
+url = "https://example.com/v{version}/zellij-{arch}.tar.gz"
+
+[zellij.arch]
+amd64 = "x86_64"
+arm64 = "aarch64"
+
+[zellij.sha256]
+amd64 = "abc"
+
+[installer]
+version = "20250101"
+source = "url"
+url = "https://example.com/install.sh"
+
+[installer.sha256]
+any = "def"
+"#,
+        )
+        .unwrap();
+
+        assert!(check_pinned(&manifest).is_err());
+        assert_eq!(
+            lock(&manifest),
+            "installer any 20250101 https://example.com/install.sh def\n\
+             zellij amd64 0.42.2 https://example.com/v0.42.2/zellij-x86_64.tar.gz abc\n\
+             zellij arm64 0.42.2 https://example.com/v0.42.2/zellij-aarch64.tar.gz -\n"
+        );
+
+        // Round-trips through the format --update-tools writes
+        let written = toml::to_string(&manifest).unwrap();
+        assert_eq!(parse(&written).unwrap(), manifest);
+
+        let mut manifest = manifest;
+        manifest
+            .get_mut("zellij")
+            .unwrap()
+            .sha256
+            .insert("arm64".to_string(), "ghi".to_string());
+        assert!(check_pinned(&manifest).is_ok());
+    }
+
+    #[test]
+    fn test_header() {
+        assert_eq!(
+            header("# a\n#\n# b\n\n[x]\nversion = \"1\"\n"),
+            "# a\n#\n# b\n"
+        );
+    }
+}
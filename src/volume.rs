@@ -0,0 +1,390 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::agent::AgentConfig;
+use crate::container::Runtime;
+
+/// Volume label marking what a klotho volume holds ("home")
+pub const VOLUME_LABEL: &str = "klotho.volume";
+
+/// Volume label with the agent a volume belongs to
+pub const VOLUME_AGENT_LABEL: &str = "klotho.volume.agent";
+
+/// Volume label with the session a volume belongs to (absent when shared by the agent)
+pub const VOLUME_SESSION_LABEL: &str = "klotho.volume.session";
+
+/// Volume label with the path under /home/agent a volume is mounted at
+pub const VOLUME_PATH_LABEL: &str = "klotho.volume.path";
+
+const AGENT_HOME: &str = "/home/agent";
+
+/// A named volume persisting a path under /home/agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HomeVolume {
+    pub name: String,
+    pub agent: String,
+    /// Owning session, `None` when shared by all of the agent's sessions
+    pub session: Option<String>,
+    /// Path relative to /home/agent
+    pub path: String,
+}
+
+impl HomeVolume {
+    pub fn new(agent: &str, session: Option<&str>, path: &str) -> Self {
+        let slug = path_slug(path);
+        let name = match session {
+            Some(session) => format!("klotho-home-{}-{}_{}", agent, session, slug),
+            None => format!("klotho-home-{}_{}", agent, slug),
+        };
+
+        HomeVolume {
+            name,
+            agent: agent.to_string(),
+            session: session.map(str::to_string),
+            path: path.to_string(),
+        }
+    }
+
+    /// Where the volume is mounted inside the container
+    pub fn destination(&self) -> String {
+        format!("{}/{}", AGENT_HOME, self.path)
+    }
+
+    /// `-v` spec mounting the volume
+    ///
+    /// podman's `U` option hands the volume to the agent user when the path
+    /// doesn't exist in the image; docker copies ownership from the image.
+    pub fn mount_spec(&self, runtime: Runtime) -> String {
+        match runtime {
+            Runtime::Podman => format!("{}:{}:U", self.name, self.destination()),
+            Runtime::Docker => format!("{}:{}", self.name, self.destination()),
+        }
+    }
+
+    /// Scope shown to the user: the session, or "agent:<agent>" when shared
+    pub fn scope(&self) -> String {
+        match &self.session {
+            Some(session) => session.clone(),
+            None => format!("agent:{}", self.agent),
+        }
+    }
+
+    fn label_args(&self) -> Vec<String> {
+        let mut labels = vec![
+            "--label=klotho=true".to_string(),
+            format!("--label={}=home", VOLUME_LABEL),
+            format!("--label={}={}", VOLUME_AGENT_LABEL, self.agent),
+            format!("--label={}={}", VOLUME_PATH_LABEL, self.path),
+        ];
+        if let Some(session) = &self.session {
+            labels.push(format!("--label={}={}", VOLUME_SESSION_LABEL, session));
+        }
+        labels
+    }
+}
+
+/// Path under /home/agent as a name part, e.g. "local-share-fish" for ".local/share/fish"
+pub fn path_slug(path: &str) -> String {
+    path.split(['/', '.'])
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Home volumes an agent's config declares for a session
+pub fn declared(config: &AgentConfig, session: &str) -> Result<Vec<HomeVolume>> {
+    let session = match config.home_volume_scope.as_str() {
+        "session" => Some(session),
+        "agent" => None,
+        other => bail!(
+            "invalid AGENT_HOME_VOLUME_SCOPE '{}' for agent '{}' (expected session or agent)",
+            other,
+            config.name
+        ),
+    };
+
+    config
+        .home_volumes
+        .iter()
+        .map(|path| {
+            let path = home_path(path).context("invalid AGENT_HOME_VOLUMES path")?;
+            Ok(HomeVolume::new(&config.name, session, path))
+        })
+        .collect()
+}
+
+/// Check a home volume path and make it relative to /home/agent
+pub fn home_path(path: &str) -> Result<&str> {
+    let path = path.trim_matches('/');
+    if path.is_empty() || path.split('/').any(|part| part == "..") {
+        bail!("'{}' must be inside /home/agent", path);
+    }
+    Ok(path)
+}
+
+/// Create a home volume unless it already exists
+pub fn ensure(runtime: Runtime, volume: &HomeVolume) -> Result<()> {
+    create_if_missing(runtime, &volume.name, &volume.label_args())
+}
+
+/// Whether a named volume exists
+pub fn exists(runtime: Runtime, name: &str) -> Result<bool> {
+    let output = runtime
+        .command()
+        .args(["volume", "inspect", name])
+        .output()
+        .context("failed to inspect volume")?;
+    Ok(output.status.success())
+}
+
+/// Create a named volume with labels unless it already exists
+pub fn create_if_missing(runtime: Runtime, name: &str, label_args: &[String]) -> Result<()> {
+    if exists(runtime, name)? {
+        return Ok(());
+    }
+
+    let output = runtime
+        .command()
+        .args(["volume", "create"])
+        .args(label_args)
+        .arg(name)
+        .output()
+        .context("failed to create volume")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to create volume {}: {}", name, stderr.trim());
+    }
+
+    Ok(())
+}
+
+/// Disk usage of volumes as reported by `system df -v`, keyed by volume name
+///
+/// Best effort: volumes missing from the output are simply absent.
+pub fn sizes(runtime: Runtime) -> HashMap<String, String> {
+    let output = match runtime.command().args(["system", "df", "-v"]).output() {
+        Ok(output) if output.status.success() => output,
+        _ => return HashMap::new(),
+    };
+    parse_df_volumes(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the volume table of `system df -v` ("NAME  LINKS  SIZE" rows)
+fn parse_df_volumes(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [name, links, size] if links.parse::<u32>().is_ok() => {
+                    Some((name.to_string(), size.to_string()))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// List klotho home volumes
+pub fn list(runtime: Runtime) -> Result<Vec<HomeVolume>> {
+    let output = runtime
+        .command()
+        .args([
+            "volume",
+            "ls",
+            "--filter",
+            &format!("label={}=home", VOLUME_LABEL),
+            "--format",
+            "{{.Name}}",
+        ])
+        .output()
+        .context("failed to list volumes")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to list volumes: {}", stderr.trim());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let names: Vec<&str> = stdout
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // One inspect for all volumes; labels come back as JSON, one line each
+    let output = runtime
+        .command()
+        .args(["volume", "inspect", "--format", "{{json .Labels}}"])
+        .args(&names)
+        .output()
+        .context("failed to inspect volumes")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to inspect volumes: {}", stderr.trim());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut volumes = Vec::new();
+    for (name, line) in names.iter().zip(stdout.lines()) {
+        let labels: Option<HashMap<String, String>> =
+            serde_json::from_str(line.trim()).context("failed to parse volume labels")?;
+        let labels = labels.unwrap_or_default();
+
+        volumes.push(HomeVolume {
+            name: name.to_string(),
+            agent: labels.get(VOLUME_AGENT_LABEL).cloned().unwrap_or_default(),
+            session: labels.get(VOLUME_SESSION_LABEL).cloned(),
+            path: labels.get(VOLUME_PATH_LABEL).cloned().unwrap_or_default(),
+        });
+    }
+
+    volumes.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(volumes)
+}
+
+/// List home volumes that belong to a single session
+pub fn session_volumes(runtime: Runtime, agent: &str, session: &str) -> Result<Vec<HomeVolume>> {
+    Ok(list(runtime)?
+        .into_iter()
+        .filter(|v| v.agent == agent && v.session.as_deref() == Some(session))
+        .collect())
+}
+
+/// Names of containers (running or stopped) that mount a volume
+pub fn users(runtime: Runtime, name: &str) -> Result<Vec<String>> {
+    let output = runtime
+        .command()
+        .args([
+            "ps",
+            "-a",
+            "--filter",
+            &format!("volume={}", name),
+            "--format",
+            "{{.Names}}",
+        ])
+        .output()
+        .context("failed to list containers")?;
+
+    if !output.status.success() {
+        bail!("failed to list containers");
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// Write the contents of a volume to a tar archive
+///
+/// tar runs in a throwaway container of `image`, as root so every file is readable.
+pub fn save(runtime: Runtime, name: &str, image: &str, archive: &Path) -> Result<()> {
+    let file =
+        File::create(archive).with_context(|| format!("failed to create {}", archive.display()))?;
+    let output = runtime
+        .command()
+        .args(["run", "--rm", "--user", "root", "--entrypoint", "tar"])
+        .arg("-v")
+        .arg(format!("{}:/data", name))
+        .arg(image)
+        .args(["-cf", "-", "-C", "/data", "."])
+        .stdout(file)
+        .stderr(Stdio::piped())
+        .output()
+        .context("failed to run tar in a container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to save volume {}: {}", name, stderr.trim());
+    }
+
+    Ok(())
+}
+
+/// Unpack a tar archive written by `save` into a volume
+pub fn restore(runtime: Runtime, name: &str, image: &str, archive: &Path) -> Result<()> {
+    let file =
+        File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+    let output = runtime
+        .command()
+        .args(["run", "--rm", "-i", "--user", "root", "--entrypoint", "tar"])
+        .arg("-v")
+        .arg(format!("{}:/data", name))
+        .arg(image)
+        .args(["-xpf", "-", "-C", "/data"])
+        .stdin(file)
+        .output()
+        .context("failed to run tar in a container")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to restore volume {}: {}", name, stderr.trim());
+    }
+
+    Ok(())
+}
+
+/// Remove a volume
+pub fn remove(runtime: Runtime, name: &str) -> Result<()> {
+    let output = runtime
+        .command()
+        .args(["volume", "rm", name])
+        .output()
+        .context("failed to remove volume")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("failed to remove volume {}: {}", name, stderr.trim());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df_volumes() {
+        let output = "Local Volumes space usage:\n\n\
+                      VOLUME NAME                LINKS     SIZE\n\
+                      klotho-cache-npm           2         312.4MB\n\
+                      klotho-home-claude_npm     0         0B\n";
+        let sizes = parse_df_volumes(output);
+        assert_eq!(
+            sizes.get("klotho-cache-npm").map(String::as_str),
+            Some("312.4MB")
+        );
+        assert_eq!(
+            sizes.get("klotho-home-claude_npm").map(String::as_str),
+            Some("0B")
+        );
+        assert_eq!(sizes.len(), 2);
+    }
+
+    #[test]
+    fn test_volume_names() {
+        let volume = HomeVolume::new("claude", Some("default"), ".local/share/fish");
+        assert_eq!(volume.name, "klotho-home-claude-default_local-share-fish");
+        assert_eq!(volume.destination(), "/home/agent/.local/share/fish");
+        assert_eq!(volume.scope(), "default");
+        assert_eq!(
+            volume.mount_spec(Runtime::Podman),
+            "klotho-home-claude-default_local-share-fish:/home/agent/.local/share/fish:U"
+        );
+
+        let shared = HomeVolume::new("claude", None, ".npm");
+        assert_eq!(shared.name, "klotho-home-claude_npm");
+        assert_eq!(shared.scope(), "agent:claude");
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::get_data_home;
+use crate::git;
+
+/// Container label holding the host path of a session's worktree
+pub const WORKTREE_LABEL: &str = "klotho.worktree";
+/// Container label holding the repository the worktree belongs to
+pub const WORKTREE_REPO_LABEL: &str = "klotho.worktree.repo";
+/// Container label holding the worktree's branch
+pub const WORKTREE_BRANCH_LABEL: &str = "klotho.worktree.branch";
+/// Container label set when klotho created the branch along with the worktree
+pub const WORKTREE_NEW_BRANCH_LABEL: &str = "klotho.worktree.new-branch";
+
+/// A git worktree backing a session's /workspace
+#[derive(Debug, Clone)]
+pub struct Worktree {
+    /// Host path of the worktree
+    pub path: PathBuf,
+    /// Top level of the repository the worktree was created from
+    pub repo: PathBuf,
+    pub branch: String,
+    /// Whether the branch was created for this worktree
+    pub new_branch: bool,
+}
+
+impl Worktree {
+    /// Container labels tying the worktree to a session
+    pub fn labels(&self) -> Vec<String> {
+        vec![
+            format!("--label={}={}", WORKTREE_LABEL, self.path.display()),
+            format!("--label={}={}", WORKTREE_REPO_LABEL, self.repo.display()),
+            format!("--label={}={}", WORKTREE_BRANCH_LABEL, self.branch),
+            format!("--label={}={}", WORKTREE_NEW_BRANCH_LABEL, self.new_branch),
+        ]
+    }
+
+    /// Rebuild worktree info from container labels
+    pub fn from_labels(labels: &HashMap<String, String>) -> Option<Self> {
+        Some(Worktree {
+            path: PathBuf::from(labels.get(WORKTREE_LABEL)?),
+            repo: PathBuf::from(labels.get(WORKTREE_REPO_LABEL)?),
+            branch: labels.get(WORKTREE_BRANCH_LABEL)?.clone(),
+            new_branch: labels.get(WORKTREE_NEW_BRANCH_LABEL).map(String::as_str) == Some("true"),
+        })
+    }
+
+    /// Host path of the repository's shared git directory
+    ///
+    /// The worktree's `.git` file points into this directory by absolute path,
+    /// so it must be mounted at the same path inside the container.
+    pub fn git_common_dir(&self) -> Result<PathBuf> {
+        let dir = PathBuf::from(git::git(&self.path, &["rev-parse", "--git-common-dir"])?);
+        let dir = if dir.is_absolute() {
+            dir
+        } else {
+            self.path.join(dir)
+        };
+        dir.canonicalize()
+            .context("failed to resolve repository git directory")
+    }
+
+    /// Count commits on the branch that no other local branch contains
+    pub fn unmerged_commits(&self) -> Result<usize> {
+        let refs = git::git(
+            &self.repo,
+            &["for-each-ref", "--format=%(refname)", "refs/heads/"],
+        )?;
+        let own_ref = format!("refs/heads/{}", self.branch);
+
+        let mut args = vec!["rev-list", "--count", self.branch.as_str(), "--not"];
+        args.extend(refs.lines().filter(|r| !r.is_empty() && *r != own_ref));
+
+        let count = git::git(&self.repo, &args)?;
+        count.parse().context("failed to count unmerged commits")
+    }
+
+    /// Remove the worktree (and its branch, if klotho created it and it's merged)
+    pub fn remove(&self) -> Result<()> {
+        let path = self.path.to_string_lossy();
+        git::git(&self.repo, &["worktree", "remove", &path])?;
+
+        if self.new_branch {
+            // -d refuses unmerged branches, which is what we want here
+            if let Err(e) = git::git(&self.repo, &["branch", "-d", &self.branch]) {
+                eprintln!("warning: kept branch '{}': {}", self.branch, e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get directory holding klotho-managed worktrees
+pub fn worktrees_dir() -> PathBuf {
+    get_data_home().join("worktrees")
+}
+
+/// Create a worktree of the repository containing `project` for a session
+///
+/// Checks out `branch` if it exists, otherwise creates it from the current HEAD.
+pub fn create(project: &Path, session_name: &str, branch: &str) -> Result<Worktree> {
+    let repo = PathBuf::from(
+        git::toplevel(project)
+            .with_context(|| format!("{} is not inside a git repository", project.display()))?,
+    );
+
+    let path = worktrees_dir().join(session_name);
+    if path.exists() {
+        anyhow::bail!(
+            "worktree directory already exists: {}\nremove it or choose another session name",
+            path.display()
+        );
+    }
+    std::fs::create_dir_all(worktrees_dir()).context("failed to create worktrees directory")?;
+
+    let branch_ref = format!("refs/heads/{}", branch);
+    let exists = git::git(&repo, &["show-ref", "--verify", "-q", &branch_ref]).is_ok();
+    let path_str = path.to_string_lossy();
+
+    if exists {
+        git::git(&repo, &["worktree", "add", "-q", &path_str, branch])?;
+    } else {
+        git::git(&repo, &["worktree", "add", "-q", "-b", branch, &path_str])?;
+    }
+
+    Ok(Worktree {
+        path,
+        repo,
+        branch: branch.to_string(),
+        new_branch: !exists,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels_roundtrip() {
+        let worktree = Worktree {
+            path: PathBuf::from("/data/klotho/worktrees/feature"),
+            repo: PathBuf::from("/home/me/repo"),
+            branch: "feature/x".to_string(),
+            new_branch: true,
+        };
+
+        let labels: HashMap<String, String> = worktree
+            .labels()
+            .iter()
+            .filter_map(|l| l.strip_prefix("--label=")?.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        let parsed = Worktree::from_labels(&labels).unwrap();
+        assert_eq!(parsed.path, worktree.path);
+        assert_eq!(parsed.repo, worktree.repo);
+        assert_eq!(parsed.branch, "feature/x");
+        assert!(parsed.new_branch);
+    }
+
+    #[test]
+    fn test_from_labels_missing() {
+        assert!(Worktree::from_labels(&HashMap::new()).is_none());
+    }
+}
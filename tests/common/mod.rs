@@ -0,0 +1,28 @@
+//! Fixtures shared by the integration tests
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Empty directory under the system temp dir, unique to this test process
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("klotho-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Run git in a directory with a fixed identity, failing the test on error
+pub fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .env("GIT_AUTHOR_NAME", "test")
+        .env("GIT_AUTHOR_EMAIL", "test@localhost")
+        .env("GIT_COMMITTER_NAME", "test")
+        .env("GIT_COMMITTER_EMAIL", "test@localhost")
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
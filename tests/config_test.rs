@@ -9,6 +9,10 @@ fn test_load_claude_config() {
             assert!(!agent_config.description.is_empty());
             assert!(!agent_config.launch_cmd.is_empty());
             assert!(!agent_config.shell.is_empty());
+            assert!(agent_config
+                .headless_cmd
+                .as_deref()
+                .is_some_and(|cmd| cmd.contains("$KLOTHO_PROMPT")));
         }
         Err(e) => {
             panic!("Failed to load claude config: {}", e);
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{git, scratch_dir};
+use klotho::snapshot;
+use std::fs;
+
+#[test]
+fn test_snapshot_rollback_roundtrip() {
+    // Snapshot state lives under XDG_STATE_HOME
+    let state = scratch_dir("state");
+    std::env::set_var("XDG_STATE_HOME", &state);
+
+    // Git project: tracked change, untracked file and a deleted file
+    let repo = scratch_dir("repo");
+    git(&repo, &["init", "-q"]);
+    fs::write(repo.join("kept.txt"), "original\n").unwrap();
+    fs::write(repo.join("removed.txt"), "tracked\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "init"]);
+
+    fs::write(repo.join("kept.txt"), "work in progress\n").unwrap();
+    fs::write(repo.join("notes.txt"), "untracked\n").unwrap();
+    fs::remove_file(repo.join("removed.txt")).unwrap();
+
+    // Plain project
+    let plain = scratch_dir("plain");
+    fs::create_dir_all(plain.join("sub")).unwrap();
+    fs::write(plain.join("sub/data.txt"), "v1\n").unwrap();
+
+    let snap = snapshot::create("test", "before", &[repo.clone(), plain.clone()]).unwrap();
+    assert_eq!(snap.dirs.len(), 2);
+
+    // Agent trashes both trees and commits in the repo
+    fs::write(repo.join("kept.txt"), "trashed\n").unwrap();
+    fs::remove_file(repo.join("notes.txt")).unwrap();
+    fs::write(repo.join("junk.txt"), "junk\n").unwrap();
+    git(&repo, &["add", "-A"]);
+    git(&repo, &["commit", "-q", "-m", "agent commit"]);
+    fs::write(plain.join("sub/data.txt"), "v2\n").unwrap();
+    fs::write(plain.join("extra.txt"), "extra\n").unwrap();
+
+    let loaded = snapshot::load("test", "before").unwrap();
+    snapshot::restore(&loaded).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(repo.join("kept.txt")).unwrap(),
+        "work in progress\n"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.join("notes.txt")).unwrap(),
+        "untracked\n"
+    );
+    assert!(!repo.join("removed.txt").exists());
+    assert!(!repo.join("junk.txt").exists());
+    assert_eq!(
+        fs::read_to_string(plain.join("sub/data.txt")).unwrap(),
+        "v1\n"
+    );
+    assert!(!plain.join("extra.txt").exists());
+
+    assert_eq!(snapshot::list("test").unwrap().len(), 1);
+
+    for dir in [state, repo, plain] {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::{git, scratch_dir};
+use klotho::worktree;
+use std::fs;
+
+#[test]
+fn test_worktree_lifecycle() {
+    // Worktrees live under XDG_DATA_HOME
+    let data = scratch_dir("wt-data");
+    std::env::set_var("XDG_DATA_HOME", &data);
+
+    let repo = scratch_dir("wt-repo");
+    git(&repo, &["init", "-q", "-b", "main"]);
+    fs::write(repo.join("README"), "hello\n").unwrap();
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "init"]);
+
+    // New branch is created from HEAD
+    let wt = worktree::create(&repo, "feature", "feature-x").unwrap();
+    assert!(wt.new_branch);
+    assert!(wt.path.join("README").exists());
+    assert!(wt.git_common_dir().unwrap().ends_with(".git"));
+    assert_eq!(wt.unmerged_commits().unwrap(), 0);
+
+    // A commit only on the worktree branch counts as unmerged
+    fs::write(wt.path.join("new.txt"), "work\n").unwrap();
+    git(&wt.path, &["add", "."]);
+    git(&wt.path, &["commit", "-q", "-m", "work"]);
+    assert_eq!(wt.unmerged_commits().unwrap(), 1);
+
+    // Once merged it can be removed along with its branch
+    git(&repo, &["merge", "-q", "--ff-only", "feature-x"]);
+    assert_eq!(wt.unmerged_commits().unwrap(), 0);
+    wt.remove().unwrap();
+    assert!(!wt.path.exists());
+
+    for dir in [data, repo] {
+        let _ = fs::remove_dir_all(dir);
+    }
+}